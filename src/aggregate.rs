@@ -0,0 +1,215 @@
+//! Aggregating the progress of many monitors into one, for example one per file of a directory copy.
+use crate::state::{Handle, State};
+use crate::Monitor;
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use std::sync::{Mutex, OnceLock};
+
+/// A [`Monitor`] that adds up the progress of its children.
+///
+/// Bytes written by a child are propagated to the group, and the group's total is the sum of
+/// its children's totals once all of them are known.
+/// Groups can be nested by attaching one group's [`monitor`](MonitorGroup::monitor) to another.
+/// The group is finished once every `MonitorGroup` handle to it is dropped.
+/// ```
+/// use write_monitor::{MonitorGroup, WriteMonitor};
+/// use std::io::Write;
+/// let group = MonitorGroup::new();
+/// for file in [&b"hello"[..], &b"world!"[..]] {
+///     let mut wm = WriteMonitor::with_total(Vec::new(), file.len() as u64);
+///     group.attach(&wm.monitor());
+///     wm.write_all(file).unwrap();
+/// }
+/// let monitor = group.monitor();
+/// assert_eq!(monitor.bytes_written(), 11);
+/// assert_eq!(monitor.total(), Some(11));
+/// assert_eq!(group.finished_children(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct MonitorGroup {
+    state: Handle,
+    children: Arc<Mutex<Vec<Monitor>>>,
+}
+
+impl Default for MonitorGroup {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MonitorGroup {
+    pub fn new() -> Self {
+        Self {
+            state: Handle::new(State::new()),
+            children: Default::default(),
+        }
+    }
+
+    /// Add `child` to the group, its bytes written so far are added to the group right away.
+    ///
+    /// Attach children before they start writing, bytes written while attaching may be missed.
+    /// Returns `false` if `child` already belongs to a group or attaching it would create a cycle.
+    pub fn attach(&self, child: &Monitor) -> bool {
+        let state = self.state.shared();
+        if !state.can_adopt(&child.state) {
+            return false;
+        }
+        let (written, unique) = (
+            child.state.bytes_written(),
+            child.state.unique_bytes_written(),
+        );
+        if child.state.node.parent.set(state.clone()).is_err() {
+            return false;
+        }
+        state.child_added(child.state.total());
+        state.add(written, unique);
+        self.lock().push(child.clone());
+        true
+    }
+
+    /// A [`Monitor`] over the combined progress of all children.
+    pub fn monitor(&self) -> Monitor {
+        Monitor::new(self.state.shared())
+    }
+
+    /// Monitors of the direct children, in the order they were attached.
+    pub fn children(&self) -> Vec<Monitor> {
+        self.lock().clone()
+    }
+
+    /// Number of direct children that are still writing.
+    pub fn active_children(&self) -> usize {
+        self.lock().iter().filter(|m| !m.is_finished()).count()
+    }
+
+    /// Number of direct children that were shut down, closed or dropped.
+    pub fn finished_children(&self) -> usize {
+        self.lock().iter().filter(|m| m.is_finished()).count()
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, Vec<Monitor>> {
+        self.children.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// A state's place in a tree of [`MonitorGroup`]s.
+#[derive(Debug, Default)]
+pub(crate) struct Node {
+    pub(crate) parent: OnceLock<Arc<State>>,
+    totals: Mutex<Totals>,
+}
+
+/// Sum of the children's known totals and how many children have no total yet.
+#[derive(Debug, Default)]
+struct Totals {
+    known: u64,
+    unknown: usize,
+}
+
+impl Totals {
+    fn add(&mut self, total: Option<u64>) {
+        match total {
+            Some(total) => self.known = self.known.saturating_add(total),
+            None => self.unknown += 1,
+        }
+    }
+
+    fn remove(&mut self, total: Option<u64>) {
+        match total {
+            Some(total) => self.known = self.known.saturating_sub(total),
+            None => self.unknown = self.unknown.saturating_sub(1),
+        }
+    }
+}
+
+impl Node {
+    pub(crate) fn parent(&self) -> Option<&Arc<State>> {
+        self.parent.get()
+    }
+}
+
+impl State {
+    /// Whether `child` can be attached to this state without creating a cycle.
+    fn can_adopt(self: &Arc<Self>, child: &Arc<State>) -> bool {
+        let mut ancestor = Some(self);
+        while let Some(state) = ancestor {
+            if Arc::ptr_eq(state, child) {
+                return false;
+            }
+            ancestor = state.node.parent();
+        }
+        true
+    }
+
+    /// A child with `total` was attached, `None` meaning unknown.
+    fn child_added(&self, total: Option<u64>) {
+        self.update_totals(|totals| totals.add(total));
+    }
+
+    /// A child's total went from `old` to `new`, `None` meaning unknown.
+    pub(crate) fn child_total_changed(&self, old: Option<u64>, new: Option<u64>) {
+        self.update_totals(|totals| {
+            totals.remove(old);
+            totals.add(new);
+        });
+    }
+
+    fn update_totals(&self, f: impl FnOnce(&mut Totals)) {
+        let total = {
+            let mut totals = self.node.totals.lock().unwrap_or_else(|e| e.into_inner());
+            f(&mut totals);
+            (totals.unknown == 0).then_some(totals.known)
+        };
+        self.set_total(total);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::MonitorGroup;
+    use crate::WriteMonitor;
+    use std::io::Write;
+
+    #[test]
+    fn total_is_unknown_while_a_child_has_none() {
+        let group = MonitorGroup::new();
+        let unknown = WriteMonitor::new(std::io::sink());
+        let known = WriteMonitor::with_total(std::io::sink(), 10);
+        assert!(group.attach(&unknown.monitor()));
+        assert!(group.attach(&known.monitor()));
+        assert_eq!(group.monitor().total(), None);
+        unknown.monitor().set_total(Some(5));
+        assert_eq!(group.monitor().total(), Some(15));
+        known.monitor().set_total(None);
+        assert_eq!(group.monitor().total(), None);
+    }
+
+    #[test]
+    fn bytes_reach_nested_groups() {
+        let outer = MonitorGroup::new();
+        let inner = MonitorGroup::new();
+        assert!(outer.attach(&inner.monitor()));
+        let mut wm = WriteMonitor::with_total(Vec::new(), 4);
+        assert!(inner.attach(&wm.monitor()));
+        wm.write_all(b"abcd").unwrap();
+        assert_eq!(outer.monitor().bytes_written(), 4);
+        assert_eq!(outer.monitor().total(), Some(4));
+        assert!(outer.monitor().is_complete());
+        drop(wm);
+        assert_eq!(inner.finished_children(), 1);
+        assert_eq!(inner.active_children(), 0);
+    }
+
+    #[test]
+    fn cycles_and_second_parents_are_refused() {
+        let a = MonitorGroup::new();
+        let b = MonitorGroup::new();
+        assert!(a.attach(&b.monitor()));
+        assert!(!b.attach(&a.monitor()));
+        assert!(!a.attach(&a.monitor()));
+        let wm = WriteMonitor::new(std::io::sink());
+        assert!(a.attach(&wm.monitor()));
+        assert!(!b.attach(&wm.monitor()));
+        assert_eq!(a.children().len(), 2);
+    }
+}
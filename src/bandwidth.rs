@@ -0,0 +1,331 @@
+//! A bandwidth budget shared by many writers.
+use crate::limit::Limiter;
+use crate::state::{Handle, State};
+use crate::{Clock, Monitor, RateLimit, WriteMonitor};
+use alloc::sync::Arc;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// Longest a member waits on the group before checking again, the group limit may have changed meanwhile.
+const RECHECK: Duration = Duration::from_secs(1);
+
+/// How long a member may go without trying to write before its share goes to the others.
+///
+/// Longer than [`RECHECK`], so members waiting on the group never count as idle.
+const IDLE: Duration = Duration::from_millis(1500);
+
+/// Sentinel stored in [`Activity::seen`] before the member's first write.
+const NEVER: u64 = u64::MAX;
+
+/// A [`RateLimit`] shared by all [`WriteMonitor`]s that [join](WriteMonitor::join) it.
+///
+/// Every member gets a share of the group's limit proportional to its weight.
+/// Members that have not tried to write for a while do not count,
+/// their share goes to the others until they write again.
+/// The group as a whole can be observed through [`BandwidthGroup::monitor`],
+/// which is finished once every `BandwidthGroup` handle is dropped and every member finished.
+/// ```
+/// use write_monitor::{BandwidthGroup, RateLimit, WriteMonitor};
+/// use std::io::Write;
+/// let group = BandwidthGroup::new(Some(RateLimit::new(1 << 20)));
+/// let mut a = WriteMonitor::new(Vec::new());
+/// let mut b = WriteMonitor::new(Vec::new());
+/// a.join(&group, 1);
+/// b.join(&group, 3);
+/// a.write_all(b"hello").unwrap();
+/// b.write_all(b"world!").unwrap();
+/// assert_eq!(group.monitor().bytes_written(), 11);
+/// assert_eq!(group.members(), 2);
+/// ```
+#[derive(Debug, Clone)]
+pub struct BandwidthGroup {
+    inner: Arc<Group>,
+    /// Keeps the group's state going while the group can still be joined.
+    handle: Handle,
+}
+
+#[derive(Debug)]
+struct Group {
+    /// Counts the bytes of all members and holds the shared token bucket.
+    state: Arc<State>,
+    /// The members that have not finished yet.
+    members: Mutex<Vec<Arc<Activity>>>,
+}
+
+impl Group {
+    fn members(&self) -> MutexGuard<'_, Vec<Arc<Activity>>> {
+        self.members.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    /// The sum of the weights of the members that tried to write recently.
+    fn active_weights(&self, now: Duration) -> u64 {
+        let now = nanos(now);
+        self.members()
+            .iter()
+            .filter(|member| {
+                let seen = member.seen.load(Ordering::Acquire);
+                seen != NEVER && now.saturating_sub(seen) < nanos(IDLE)
+            })
+            .map(|member| member.weight)
+            .sum()
+    }
+}
+
+/// What the group knows about a member to split its limit.
+#[derive(Debug)]
+struct Activity {
+    weight: u64,
+    /// Clock reading in nanoseconds of the member's last attempt to write, [`NEVER`] before the first.
+    seen: AtomicU64,
+}
+
+fn nanos(duration: Duration) -> u64 {
+    duration.as_nanos().min(NEVER as u128 - 1) as u64
+}
+
+impl BandwidthGroup {
+    pub fn new(limit: Option<RateLimit>) -> Self {
+        Self::with_state(State::new(), limit)
+    }
+
+    /// Create a `BandwidthGroup` that refills its limit and measures rates with `clock`.
+    pub fn with_clock(limit: Option<RateLimit>, clock: impl Clock + 'static) -> Self {
+        Self::with_state(State::with_clock(Arc::new(clock)), limit)
+    }
+
+    fn with_state(state: State, limit: Option<RateLimit>) -> Self {
+        state.set_rate_limit(limit);
+        let handle = Handle::new(state);
+        Self {
+            inner: Arc::new(Group {
+                state: handle.shared(),
+                members: Default::default(),
+            }),
+            handle,
+        }
+    }
+
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.inner.state.limiter.get()
+    }
+
+    /// Change the limit of the whole group, members pick it up within a second.
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.inner.state.set_rate_limit(limit)
+    }
+
+    /// Number of members that have not finished yet.
+    pub fn members(&self) -> usize {
+        self.inner.members().len()
+    }
+
+    /// A [`Monitor`] over the bytes written by all members.
+    pub fn monitor(&self) -> Monitor {
+        Monitor::new(self.inner.state.clone())
+    }
+}
+
+/// A state's membership in a [`BandwidthGroup`].
+#[derive(Debug)]
+pub(crate) struct Membership {
+    group: Arc<Group>,
+    activity: Arc<Activity>,
+    /// This member's part of the group's limit, kept in step with the group's limit and active weights.
+    share: Limiter,
+    /// Keeps the group's state going until the member finished.
+    handle: Mutex<Option<Handle>>,
+}
+
+impl Membership {
+    /// Take up to `want` bytes worth of tokens from both this member's share and the group.
+    pub(crate) fn take(&self, want: u64) -> Result<u64, Duration> {
+        let group = &self.group;
+        let Some(limit) = group.state.limiter.get() else {
+            return Ok(want);
+        };
+        let now = group.state.meter.now();
+        self.activity.seen.store(nanos(now), Ordering::Release);
+        let weight = self.activity.weight;
+        let weights = group.active_weights(now).max(weight);
+        let scale = |n: u64| (n as u128 * weight as u128 / weights as u128) as u64;
+        let share = RateLimit {
+            bytes_per_second: scale(limit.bytes_per_second),
+            burst: scale(limit.burst).max(1),
+        };
+        if self.share.get() != Some(share) {
+            self.share.set(Some(share), now);
+        }
+        let granted = self
+            .share
+            .take(want, now)
+            .map_err(|wait| wait.min(RECHECK))?;
+        match group.state.limiter.take(granted, now) {
+            Ok(shared) => {
+                self.share.release(granted - shared);
+                Ok(shared)
+            }
+            Err(wait) => {
+                self.share.release(granted);
+                Err(wait.min(RECHECK))
+            }
+        }
+    }
+
+    pub(crate) fn release(&self, n: u64) {
+        self.share.release(n);
+        self.group.state.limiter.release(n);
+    }
+
+    /// Count `n` bytes written by this member towards the group, members write to separate streams.
+    pub(crate) fn record(&self, n: u64) {
+        self.group.state.add(n, n);
+    }
+
+    /// Stop counting towards the group's weights once the member finished.
+    pub(crate) fn leave(&self) {
+        let handle = self.handle.lock().unwrap_or_else(|e| e.into_inner()).take();
+        if handle.is_some() {
+            self.group
+                .members()
+                .retain(|member| !Arc::ptr_eq(member, &self.activity));
+        }
+    }
+}
+
+impl<W> WriteMonitor<W> {
+    /// Share the bandwidth of `group` with its other members, in proportion to `weight`.
+    ///
+    /// A writer can only be in one group, returns `false` if it already joined one.
+    /// The writer's own [`RateLimit`] still applies on top of the group's.
+    pub fn join(&self, group: &BandwidthGroup, weight: u64) -> bool {
+        let activity = Arc::new(Activity {
+            weight: weight.max(1),
+            seen: AtomicU64::new(NEVER),
+        });
+        let membership = Membership {
+            group: group.inner.clone(),
+            activity: activity.clone(),
+            share: Limiter::default(),
+            handle: Mutex::new(Some(group.handle.clone())),
+        };
+        if self.state.bandwidth.set(membership).is_err() {
+            return false;
+        }
+        group.inner.members().push(activity);
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{BandwidthGroup, RateLimit, WriteMonitor};
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    fn group() -> BandwidthGroup {
+        BandwidthGroup::new(Some(RateLimit::new(1000).with_burst(100)))
+    }
+
+    #[test]
+    fn members_are_held_to_the_group_limit() {
+        let group = group();
+        let start = Instant::now();
+        let writers: Vec<_> = (0..2)
+            .map(|_| {
+                let mut wm = WriteMonitor::new(Vec::new());
+                wm.join(&group, 1);
+                std::thread::spawn(move || wm.write_all(&[0; 200]).unwrap())
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        // A burst of 100, then 300 more at 1000 bytes per second.
+        assert!(start.elapsed() >= Duration::from_millis(250));
+        assert_eq!(group.monitor().bytes_written(), 400);
+        assert_eq!(group.monitor().unique_bytes_written(), 400);
+        assert_eq!(group.members(), 0);
+    }
+
+    #[test]
+    fn members_count_towards_the_group_once() {
+        let group = BandwidthGroup::new(None);
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut wm = WriteMonitor::new(std::io::sink());
+                wm.join(&group, 1);
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        wm.write_all(&[0; 10]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        let monitor = group.monitor();
+        assert_eq!(monitor.bytes_written(), 40_000);
+        assert_eq!(monitor.unique_bytes_written(), 40_000);
+    }
+
+    #[test]
+    fn the_group_finishes_once_dropped_and_left() {
+        let group = group();
+        let monitor = group.monitor();
+        let wm = WriteMonitor::new(std::io::sink());
+        wm.join(&group, 1);
+        drop(group);
+        assert!(!monitor.is_finished());
+        drop(wm);
+        assert!(monitor.is_finished());
+    }
+
+    #[cfg(feature = "futures")]
+    mod wait {
+        use crate::{BandwidthGroup, Clock, ManualClock, RateLimit, Sleep, Timer, WriteMonitor};
+        use futures::io::AsyncWriteExt;
+        use std::time::Duration;
+
+        /// A timer that moves the clock forward instead of sleeping.
+        struct Advance(ManualClock);
+
+        impl Timer for Advance {
+            fn sleep(&self, duration: Duration) -> Sleep {
+                self.0.advance(duration);
+                Box::pin(core::future::ready(()))
+            }
+        }
+
+        #[test]
+        fn idle_members_leave_their_share_to_the_others() {
+            let clock = ManualClock::new();
+            let group = BandwidthGroup::with_clock(
+                Some(RateLimit::new(1000).with_burst(100)),
+                clock.clone(),
+            );
+            let member = || {
+                let mut wm = WriteMonitor::new(Vec::new());
+                wm.set_timer(Advance(clock.clone()));
+                wm.join(&group, 1);
+                wm
+            };
+            let (mut busy, mut idle) = (member(), member());
+            // A member that never wrote leaves the full limit to the others:
+            // a burst of 100, then 200 more at 1000 bytes per second.
+            futures::executor::block_on(busy.write_all(&[0; 300])).unwrap();
+            assert_eq!(clock.now(), Duration::from_millis(200));
+            futures::executor::block_on(idle.write_all(&[0; 1])).unwrap();
+            let start = clock.now();
+            futures::executor::block_on(busy.write_all(&[0; 1500])).unwrap();
+            // Half the limit while the other member counts as active, 750 bytes in 1.5s,
+            // then the full limit for the rest.
+            let elapsed = clock.now() - start;
+            assert!(elapsed > Duration::from_millis(1500), "{elapsed:?}");
+            assert!(elapsed < Duration::from_millis(2500), "{elapsed:?}");
+            assert_eq!(group.members(), 2);
+        }
+    }
+}
@@ -0,0 +1,76 @@
+//! Driving [`indicatif`] progress bars from a [`Monitor`].
+use crate::{Monitor, WriteMonitor};
+use indicatif::{MultiProgress, ProgressBar};
+use std::time::Duration;
+
+/// How often an attached bar is updated at most.
+const REFRESH: Duration = Duration::from_millis(50);
+/// How long to wait for a change before updating an attached bar anyway, so the rate keeps moving.
+const TICK: Duration = Duration::from_millis(500);
+
+impl Monitor {
+    /// Keep `bar` in sync with this monitor from a background thread and return it.
+    ///
+    /// The bar's position follows [`Monitor::unique_bytes_written`] and its length [`Monitor::total`].
+    /// When the writer finishes the bar is finished with the message "done",
+    /// or abandoned with the error if it failed, was cancelled or dropped halfway.
+    /// ```
+    /// use indicatif::ProgressBar;
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// let mut wm = WriteMonitor::with_total(Vec::new(), 5);
+    /// let bar = wm.monitor().attach_progress_bar(ProgressBar::hidden());
+    /// wm.write_all(b"hello").unwrap();
+    /// drop(wm);
+    /// while !bar.is_finished() {
+    ///     std::thread::sleep(std::time::Duration::from_millis(10));
+    /// }
+    /// assert_eq!(bar.position(), 5);
+    /// assert_eq!(bar.message(), "done");
+    /// ```
+    #[cfg_attr(docsrs, doc(cfg(feature = "indicatif")))]
+    pub fn attach_progress_bar(&self, bar: ProgressBar) -> ProgressBar {
+        let monitor = self.clone();
+        let driven = bar.clone();
+        std::thread::spawn(move || monitor.drive(&driven));
+        bar
+    }
+
+    /// Add `bar` to `multi` and keep it in sync with this monitor, see [`Monitor::attach_progress_bar`].
+    ///
+    /// Attach every monitor of a transfer to the same [`MultiProgress`] to show one bar per monitor.
+    #[cfg_attr(docsrs, doc(cfg(feature = "indicatif")))]
+    pub fn attach_multi_progress(&self, multi: &MultiProgress, bar: ProgressBar) -> ProgressBar {
+        self.attach_progress_bar(multi.add(bar))
+    }
+
+    fn drive(&self, bar: &ProgressBar) {
+        loop {
+            let finished = self.is_finished();
+            match self.total() {
+                Some(total) => bar.set_length(total),
+                None => bar.unset_length(),
+            }
+            bar.set_position(self.unique_bytes_written());
+            if finished {
+                break;
+            }
+            self.blocking_changed_timeout(TICK);
+            std::thread::sleep(REFRESH);
+        }
+        let lifecycle = self.lifecycle();
+        let message = lifecycle.outcome(self);
+        match lifecycle.is_abandoned() {
+            true => bar.abandon_with_message(message),
+            false => bar.finish_with_message(message),
+        }
+    }
+}
+
+impl<W> WriteMonitor<W> {
+    /// Keep `bar` in sync with this writer, see [`Monitor::attach_progress_bar`].
+    #[cfg_attr(docsrs, doc(cfg(feature = "indicatif")))]
+    pub fn attach_progress_bar(&self, bar: ProgressBar) -> ProgressBar {
+        self.monitor().attach_progress_bar(bar)
+    }
+}
@@ -0,0 +1,160 @@
+//! Cancelling a monitored transfer from the [`Monitor`] side.
+use crate::Monitor;
+use alloc::string::String;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::io;
+use std::sync::Mutex;
+
+/// The error returned by writes to a [`WriteMonitor`](crate::WriteMonitor) after [`Monitor::cancel`].
+///
+/// It is wrapped in an [`io::Error`] of kind [`io::ErrorKind::Other`], use [`Cancelled::find`] to recognise it.
+/// ```
+/// use write_monitor::{Cancelled, Lifecycle, WriteMonitor};
+/// use std::io::Write;
+/// let mut wm = WriteMonitor::new(Vec::new());
+/// let monitor = wm.monitor();
+/// monitor.cancel_with_reason("user aborted");
+/// let err = wm.write(b"hello").unwrap_err();
+/// assert_eq!(Cancelled::find(&err).and_then(Cancelled::reason), Some("user aborted"));
+/// assert_eq!(monitor.lifecycle(), Lifecycle::Cancelled);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cancelled {
+    reason: Option<String>,
+}
+
+impl Cancelled {
+    pub fn reason(&self) -> Option<&str> {
+        self.reason.as_deref()
+    }
+
+    /// The `Cancelled` inside `error`, if it is one.
+    pub fn find(error: &io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl core::fmt::Display for Cancelled {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        match &self.reason {
+            Some(reason) => write!(f, "write cancelled: {reason}"),
+            None => f.write_str("write cancelled"),
+        }
+    }
+}
+
+impl std::error::Error for Cancelled {}
+
+#[derive(Debug, Default)]
+pub(crate) struct Cancellation {
+    cancelled: AtomicBool,
+    reason: Mutex<Option<String>>,
+}
+
+impl Cancellation {
+    fn reason(&self) -> Option<String> {
+        self.reason
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+
+    /// The error to fail writes with once cancelled.
+    pub(crate) fn error(&self) -> Option<io::Error> {
+        if !self.cancelled.load(Ordering::Acquire) {
+            return None;
+        }
+        let reason = self.reason();
+        Some(io::Error::other(Cancelled { reason }))
+    }
+}
+
+impl Monitor {
+    /// Cancel the transfer, further writes fail with [`Cancelled`] and parked async writers are woken.
+    ///
+    /// This does nothing once the writer finished, a transfer that ended keeps its [`Lifecycle`](crate::Lifecycle).
+    /// ```
+    /// use write_monitor::{Lifecycle, WriteMonitor};
+    /// use std::io::Write;
+    /// let mut wm = WriteMonitor::with_total(Vec::new(), 5);
+    /// let monitor = wm.monitor();
+    /// wm.write_all(b"hello").unwrap();
+    /// drop(wm);
+    /// monitor.cancel();
+    /// assert_eq!(monitor.lifecycle(), Lifecycle::Closed);
+    /// assert!(!monitor.is_cancelled());
+    /// ```
+    pub fn cancel(&self) {
+        self.cancel_inner(None)
+    }
+
+    /// Like [`Monitor::cancel`] with a reason that is passed on in the [`Cancelled`] error.
+    ///
+    /// The first reason given is kept, cancelling again does not replace it.
+    pub fn cancel_with_reason(&self, reason: impl Into<String>) {
+        self.cancel_inner(Some(reason.into()))
+    }
+
+    fn cancel_inner(&self, reason: Option<String>) {
+        if self.state.is_finished() {
+            return;
+        }
+        let cancel = &self.state.cancel;
+        {
+            let mut current = cancel.reason.lock().unwrap_or_else(|e| e.into_inner());
+            if current.is_none() {
+                *current = reason;
+            }
+        }
+        cancel.cancelled.store(true, Ordering::Release);
+        self.state.lifecycle.cancelled();
+        self.state.notify();
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.state.cancel.cancelled.load(Ordering::Acquire)
+    }
+
+    /// The reason given to [`Monitor::cancel_with_reason`].
+    pub fn cancel_reason(&self) -> Option<String> {
+        self.state.cancel.reason()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Lifecycle, WriteMonitor};
+    use std::io::Write;
+
+    #[test]
+    fn the_first_reason_is_kept() {
+        let wm = WriteMonitor::new(std::io::sink());
+        let monitor = wm.monitor();
+        monitor.cancel_with_reason("disk full");
+        monitor.cancel();
+        monitor.cancel_with_reason("user aborted");
+        assert_eq!(monitor.cancel_reason().as_deref(), Some("disk full"));
+        assert_eq!(monitor.lifecycle(), Lifecycle::Cancelled);
+    }
+
+    #[test]
+    fn a_reason_can_follow_a_plain_cancel() {
+        let wm = WriteMonitor::new(std::io::sink());
+        let monitor = wm.monitor();
+        monitor.cancel();
+        monitor.cancel_with_reason("user aborted");
+        assert_eq!(monitor.cancel_reason().as_deref(), Some("user aborted"));
+    }
+
+    #[test]
+    fn finished_writers_are_not_cancelled() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let monitor = wm.monitor();
+        wm.write_all(b"hello").unwrap();
+        drop(wm);
+        monitor.cancel_with_reason("too late");
+        assert_eq!(monitor.lifecycle(), Lifecycle::Dropped);
+        assert!(!monitor.is_cancelled());
+        assert_eq!(monitor.cancel_reason(), None);
+    }
+}
@@ -0,0 +1,102 @@
+//! Counting the errors returned by the inner writer.
+use crate::Monitor;
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::io::ErrorKind;
+use std::sync::Mutex;
+
+#[derive(Debug, Default)]
+pub(crate) struct Errors {
+    failed: AtomicU64,
+    would_block: AtomicU64,
+    interrupted: AtomicU64,
+    last: Mutex<Option<ErrorKind>>,
+}
+
+impl Errors {
+    /// Count an error, returns `false` for the kinds callers are expected to retry.
+    pub(crate) fn record(&self, kind: ErrorKind) -> bool {
+        *self.last.lock().unwrap_or_else(|e| e.into_inner()) = Some(kind);
+        let counter = match kind {
+            ErrorKind::WouldBlock => &self.would_block,
+            ErrorKind::Interrupted => &self.interrupted,
+            _ => &self.failed,
+        };
+        counter.fetch_add(1, Ordering::AcqRel);
+        !matches!(kind, ErrorKind::WouldBlock | ErrorKind::Interrupted)
+    }
+}
+
+impl Monitor {
+    /// Number of writes, flushes and shutdowns that failed,
+    /// not counting [`ErrorKind::WouldBlock`] and [`ErrorKind::Interrupted`].
+    pub fn error_count(&self) -> u64 {
+        self.state.errors.failed.load(Ordering::Acquire)
+    }
+
+    /// Number of times the inner writer returned [`ErrorKind::WouldBlock`].
+    pub fn would_block_count(&self) -> u64 {
+        self.state.errors.would_block.load(Ordering::Acquire)
+    }
+
+    /// Number of times the inner writer returned [`ErrorKind::Interrupted`].
+    pub fn interrupted_count(&self) -> u64 {
+        self.state.errors.interrupted.load(Ordering::Acquire)
+    }
+
+    /// The kind of the most recent error returned by the inner writer, including retried ones.
+    pub fn last_error(&self) -> Option<ErrorKind> {
+        *self
+            .state
+            .errors
+            .last
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Lifecycle, WriteMonitor};
+    use std::collections::VecDeque;
+    use std::io::{self, ErrorKind, Write};
+
+    /// A writer that fails with the given kinds in turn, then accepts everything.
+    struct Failing(VecDeque<ErrorKind>);
+
+    impl Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.0.pop_front() {
+                Some(kind) => Err(kind.into()),
+                None => Ok(buf.len()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn retried_errors_are_counted_apart() {
+        use ErrorKind::*;
+        let failing = Failing([WouldBlock, Interrupted, Interrupted, BrokenPipe].into());
+        let mut wm = WriteMonitor::new(failing);
+        let monitor = wm.monitor();
+        assert_eq!(monitor.last_error(), None);
+        assert_eq!(wm.write(b"abc").unwrap_err().kind(), WouldBlock);
+        assert_eq!(monitor.lifecycle(), Lifecycle::Active);
+        // write_all retries the interrupted writes on its own.
+        assert_eq!(wm.write_all(b"abc").unwrap_err().kind(), BrokenPipe);
+        assert_eq!(monitor.would_block_count(), 1);
+        assert_eq!(monitor.interrupted_count(), 2);
+        assert_eq!(monitor.error_count(), 1);
+        assert_eq!(monitor.last_error(), Some(BrokenPipe));
+        assert_eq!(monitor.lifecycle(), Lifecycle::Errored(BrokenPipe));
+        assert_eq!(monitor.bytes_written(), 0);
+        wm.write_all(b"abc").unwrap();
+        assert_eq!(monitor.bytes_written(), 3);
+        assert_eq!(monitor.lifecycle(), Lifecycle::Active);
+        assert_eq!(monitor.error_count(), 1);
+        assert_eq!(monitor.last_error(), Some(BrokenPipe));
+    }
+}
@@ -0,0 +1,114 @@
+//! The parts of the stream a writer has written, for counting unique bytes across seeks.
+use core::ops::Range;
+
+/// Sorted, disjoint and non-adjacent ranges of the stream that were written.
+///
+/// A writer that never seeks keeps a single range, every hole it leaves behind by seeking adds one.
+#[derive(Debug, Default)]
+pub(crate) struct Written(Vec<Range<u64>>);
+
+impl Written {
+    /// Everything before `offset` counts as written, nothing after it.
+    pub(crate) fn reset(&mut self, offset: u64) {
+        self.0.clear();
+        if offset > 0 {
+            self.0.push(0..offset);
+        }
+    }
+
+    /// Length of the start of the stream that was written, up to the first hole.
+    pub(crate) fn prefix(&self) -> u64 {
+        self.0.first().filter(|r| r.start == 0).map_or(0, |r| r.end)
+    }
+
+    /// Mark `range` as written, returning how many of its bytes were not written before.
+    pub(crate) fn insert(&mut self, range: Range<u64>) -> u64 {
+        if range.is_empty() {
+            return 0;
+        }
+        let first = self.0.partition_point(|r| r.end < range.start);
+        let last = self.0.partition_point(|r| r.start <= range.end);
+        let touched = &self.0[first..last];
+        let overlap: u64 = touched
+            .iter()
+            .map(|r| {
+                r.end
+                    .min(range.end)
+                    .saturating_sub(r.start.max(range.start))
+            })
+            .sum();
+        let start = touched
+            .first()
+            .map_or(range.start, |r| r.start.min(range.start));
+        let end = touched.last().map_or(range.end, |r| r.end.max(range.end));
+        self.0.splice(first..last, core::iter::once(start..end));
+        range.end - range.start - overlap
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Written;
+    use crate::WriteMonitor;
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    #[test]
+    fn insert_counts_new_bytes_only() {
+        let mut written = Written::default();
+        assert_eq!(written.insert(100..150), 50);
+        assert_eq!(written.insert(0..10), 10);
+        assert_eq!(written.insert(5..120), 90);
+        assert_eq!((written.0.len(), written.0[0].clone()), (1, 0..150));
+        assert_eq!(written.insert(150..160), 10);
+        assert_eq!(written.insert(200..200), 0);
+        assert_eq!((written.0.len(), written.0[0].clone()), (1, 0..160));
+        written.reset(20);
+        assert_eq!(written.insert(10..30), 10);
+    }
+
+    #[test]
+    fn filling_a_hole_left_by_seeking_forward_counts() {
+        let mut wm = WriteMonitor::with_total(Cursor::new(Vec::new()), 150);
+        let monitor = wm.monitor();
+        wm.seek(SeekFrom::Start(100)).unwrap();
+        wm.write_all(&[0; 50]).unwrap();
+        assert_eq!(monitor.unique_bytes_written(), 50);
+        assert_eq!(monitor.high_water_mark(), 150);
+        assert!(!monitor.is_complete());
+        wm.seek(SeekFrom::Start(0)).unwrap();
+        wm.write_all(&[0; 100]).unwrap();
+        assert_eq!(monitor.unique_bytes_written(), 150);
+        assert!(monitor.is_complete());
+    }
+
+    #[test]
+    fn seeking_alone_writes_nothing() {
+        let mut wm = WriteMonitor::new(Cursor::new(Vec::new()));
+        let monitor = wm.monitor();
+        wm.seek(SeekFrom::Start(100)).unwrap();
+        assert_eq!(monitor.position(), 100);
+        assert_eq!(monitor.high_water_mark(), 0);
+        assert_eq!(monitor.unique_bytes_written(), 0);
+    }
+
+    #[test]
+    fn shared_writers_count_every_byte_once() {
+        let monitor = WriteMonitor::new(std::io::sink()).monitor();
+        let writers: Vec<_> = (0..4)
+            .map(|_| {
+                let mut wm = WriteMonitor::with_monitor(std::io::sink(), &monitor);
+                std::thread::spawn(move || {
+                    for _ in 0..1000 {
+                        wm.write_all(&[0; 10]).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for writer in writers {
+            writer.join().unwrap();
+        }
+        assert_eq!(monitor.bytes_written(), 40_000);
+        assert_eq!(monitor.unique_bytes_written(), 40_000);
+        assert_eq!(monitor.high_water_mark(), 40_000);
+    }
+}
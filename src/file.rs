@@ -0,0 +1,445 @@
+//! Monitoring writes to files, counting bytes as done once they are synced to disk.
+use crate::{Monitor, WriteMonitor};
+use std::fs::File;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::time::Duration;
+
+/// When a [`FileMonitor`] calls [`File::sync_data`] on its own.
+///
+/// A sync is due once either `bytes` were written or `interval` passed since the last one.
+/// With neither set the file is only synced by [`FileMonitor::sync`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub struct SyncPolicy {
+    pub bytes: Option<u64>,
+    pub interval: Option<Duration>,
+}
+
+impl SyncPolicy {
+    /// Sync every `bytes` bytes.
+    pub fn every_bytes(bytes: u64) -> Self {
+        Self {
+            bytes: Some(bytes),
+            interval: None,
+        }
+    }
+
+    /// Sync every `interval`, checked on every write.
+    pub fn every(interval: Duration) -> Self {
+        Self {
+            bytes: None,
+            interval: Some(interval),
+        }
+    }
+
+    pub fn with_bytes(self, bytes: u64) -> Self {
+        Self {
+            bytes: Some(bytes),
+            ..self
+        }
+    }
+
+    pub fn with_interval(self, interval: Duration) -> Self {
+        Self {
+            interval: Some(interval),
+            ..self
+        }
+    }
+}
+
+/// Counters of a writer at the start of a sync.
+#[derive(Debug, Clone, Copy)]
+struct Mark {
+    /// Bytes written, overwrites included, which the [`SyncPolicy`] counts.
+    written: u64,
+    /// Distinct bytes written, which are flushed.
+    unique: u64,
+    /// Length of the start of the stream written without holes, which is synced.
+    prefix: u64,
+}
+
+impl Mark {
+    fn new<W>(writer: &WriteMonitor<W>) -> Self {
+        Self {
+            written: writer.state.bytes_written(),
+            unique: writer.state.unique_bytes_written(),
+            prefix: writer.state.written_prefix(),
+        }
+    }
+}
+
+/// Where the last sync left off.
+#[derive(Debug)]
+struct Synced {
+    policy: SyncPolicy,
+    /// Bytes written at the last sync, overwrites included.
+    bytes: u64,
+    /// Clock reading of the last sync.
+    at: Duration,
+}
+
+impl Synced {
+    fn new<W>(writer: &WriteMonitor<W>, policy: SyncPolicy) -> Self {
+        Self {
+            policy,
+            bytes: writer.state.bytes_written(),
+            at: writer.state.meter.now(),
+        }
+    }
+
+    fn is_due<W>(&self, writer: &WriteMonitor<W>) -> bool {
+        let written = writer.state.bytes_written();
+        if written == self.bytes {
+            return false;
+        }
+        let bytes = self
+            .policy
+            .bytes
+            .is_some_and(|n| written.saturating_sub(self.bytes) >= n);
+        let now = writer.state.meter.now();
+        let interval = self
+            .policy
+            .interval
+            .is_some_and(|i| now.saturating_sub(self.at) >= i);
+        bytes || interval
+    }
+
+    fn record<W>(&mut self, writer: &WriteMonitor<W>, r: &io::Result<()>, mark: Mark) {
+        match r {
+            Ok(()) => {
+                writer.state.flushed_up_to(mark.unique);
+                writer.state.synced_up_to(mark.prefix);
+                self.bytes = mark.written;
+                self.at = writer.state.meter.now();
+            }
+            Err(e) => writer.state.failed(e.kind()),
+        }
+    }
+}
+
+/// A [`WriteMonitor`] over a [`File`] that calls [`File::sync_data`] according to a [`SyncPolicy`].
+///
+/// Synced bytes are reported by [`Monitor::bytes_synced`], which is the safe point to resume from after a crash.
+/// It stops at the first hole left by seeking forward, even if bytes past it were synced.
+/// A due sync runs before the next write, so a failed sync never loses track of written bytes.
+/// Call [`FileMonitor::sync`] or [`FileMonitor::finish`] at the end to sync the tail.
+/// ```
+/// use write_monitor::{FileMonitor, SyncPolicy};
+/// use std::io::Write;
+/// let file = std::fs::File::create(std::env::temp_dir().join("write-monitor-sync")).unwrap();
+/// let mut fm = FileMonitor::new(file, SyncPolicy::every_bytes(4));
+/// let monitor = fm.monitor();
+/// fm.write_all(b"hello").unwrap();
+/// assert_eq!(monitor.bytes_synced(), 0);
+/// fm.write_all(b" world").unwrap();
+/// assert_eq!(monitor.bytes_synced(), 5);
+/// fm.finish().unwrap();
+/// assert_eq!(monitor.bytes_synced(), 11);
+/// ```
+#[derive(Debug)]
+pub struct FileMonitor {
+    writer: WriteMonitor<File>,
+    synced: Synced,
+}
+
+impl FileMonitor {
+    pub fn new(file: File, policy: SyncPolicy) -> Self {
+        Self::with_writer(WriteMonitor::new(file), policy)
+    }
+
+    /// Sync an already configured `WriteMonitor`, for example one created with a total or an offset.
+    pub fn with_writer(writer: WriteMonitor<File>, policy: SyncPolicy) -> Self {
+        Self {
+            synced: Synced::new(&writer, policy),
+            writer,
+        }
+    }
+
+    pub fn monitor(&self) -> Monitor {
+        self.writer.monitor()
+    }
+
+    pub fn get_ref(&self) -> &File {
+        self.writer.get_ref()
+    }
+
+    /// Flush and sync everything written so far.
+    pub fn sync(&mut self) -> io::Result<()> {
+        self.writer.flush()?;
+        let mark = Mark::new(&self.writer);
+        let r = self.writer.get_ref().sync_data();
+        self.synced.record(&self.writer, &r, mark);
+        r
+    }
+
+    /// Sync everything written and take the file back, this finishes the [`Monitor`].
+    pub fn finish(mut self) -> io::Result<File> {
+        self.sync()?;
+        Ok(self.writer.into_inner())
+    }
+
+    fn sync_if_due(&mut self) -> io::Result<()> {
+        if self.synced.is_due(&self.writer) {
+            self.sync()?;
+        }
+        Ok(())
+    }
+}
+
+impl Write for FileMonitor {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.sync_if_due()?;
+        self.writer.write(buf)
+    }
+
+    fn write_vectored(&mut self, bufs: &[io::IoSlice<'_>]) -> io::Result<usize> {
+        self.sync_if_due()?;
+        self.writer.write_vectored(bufs)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+impl Seek for FileMonitor {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        self.writer.seek(pos)
+    }
+}
+
+#[cfg(feature = "tokio-fs")]
+pub use tokio_file::TokioFileMonitor;
+
+#[cfg(feature = "tokio-fs")]
+mod tokio_file {
+    use super::{Mark, SyncPolicy, Synced};
+    use crate::{Monitor, WriteMonitor};
+    use core::pin::Pin;
+    use core::task::{ready, Context, Poll};
+    use std::future::Future;
+    use std::io;
+    use std::sync::Arc;
+    use tokio::fs::File;
+    use tokio::io::AsyncWrite;
+    use tokio::task::JoinHandle;
+
+    /// The tokio version of [`FileMonitor`](crate::FileMonitor).
+    ///
+    /// Syncs run on tokio's blocking thread pool, a due sync holds back the next write until it is done.
+    /// Needs the `tokio-fs` feature.
+    #[derive(Debug)]
+    pub struct TokioFileMonitor {
+        writer: WriteMonitor<File>,
+        /// A second handle to the file for syncing while `writer` owns the first.
+        file: Arc<std::fs::File>,
+        synced: Synced,
+        /// The sync in progress and the counters when it started.
+        syncing: Option<(Mark, JoinHandle<io::Result<()>>)>,
+    }
+
+    impl TokioFileMonitor {
+        pub async fn new(file: File, policy: SyncPolicy) -> io::Result<Self> {
+            Self::with_writer(WriteMonitor::new(file), policy).await
+        }
+
+        /// Sync an already configured `WriteMonitor`, for example one created with a total or an offset.
+        pub async fn with_writer(
+            writer: WriteMonitor<File>,
+            policy: SyncPolicy,
+        ) -> io::Result<Self> {
+            let file = writer.get_ref().try_clone().await?.into_std().await;
+            Ok(Self {
+                synced: Synced::new(&writer, policy),
+                writer,
+                file: Arc::new(file),
+                syncing: None,
+            })
+        }
+
+        pub fn monitor(&self) -> Monitor {
+            self.writer.monitor()
+        }
+
+        pub fn get_ref(&self) -> &File {
+            self.writer.get_ref()
+        }
+
+        /// Flush and sync everything written so far.
+        pub async fn sync(&mut self) -> io::Result<()> {
+            core::future::poll_fn(|cx| self.poll_sync(cx, true)).await
+        }
+
+        /// Sync everything written and take the file back, this finishes the [`Monitor`].
+        pub async fn finish(mut self) -> io::Result<File> {
+            self.sync().await?;
+            Ok(self.writer.into_inner())
+        }
+
+        /// Finish the sync in progress, if any.
+        fn poll_syncing(&mut self, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            if let Some((mark, task)) = &mut self.syncing {
+                let r =
+                    ready!(Pin::new(task).poll(cx)).unwrap_or_else(|e| Err(io::Error::other(e)));
+                self.synced.record(&self.writer, &r, *mark);
+                self.syncing = None;
+                r?;
+            }
+            Poll::Ready(Ok(()))
+        }
+
+        /// Finish the sync in progress, then start and finish another one if `force`d or due.
+        fn poll_sync(&mut self, cx: &mut Context<'_>, mut force: bool) -> Poll<io::Result<()>> {
+            loop {
+                ready!(self.poll_syncing(cx))?;
+                if !force && !self.synced.is_due(&self.writer) {
+                    return Poll::Ready(Ok(()));
+                }
+                ready!(Pin::new(&mut self.writer).poll_flush(cx))?;
+                let mark = Mark::new(&self.writer);
+                let file = self.file.clone();
+                let task = tokio::task::spawn_blocking(move || file.sync_data());
+                self.syncing = Some((mark, task));
+                force = false;
+            }
+        }
+    }
+
+    impl AsyncWrite for TokioFileMonitor {
+        fn poll_write(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            buf: &[u8],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            ready!(this.poll_sync(cx, false))?;
+            Pin::new(&mut this.writer).poll_write(cx, buf)
+        }
+
+        fn poll_write_vectored(
+            self: Pin<&mut Self>,
+            cx: &mut Context<'_>,
+            bufs: &[io::IoSlice<'_>],
+        ) -> Poll<io::Result<usize>> {
+            let this = self.get_mut();
+            ready!(this.poll_sync(cx, false))?;
+            Pin::new(&mut this.writer).poll_write_vectored(cx, bufs)
+        }
+
+        fn is_write_vectored(&self) -> bool {
+            self.writer.is_write_vectored()
+        }
+
+        fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            Pin::new(&mut self.get_mut().writer).poll_flush(cx)
+        }
+
+        /// Waits for a sync in progress, then shuts the writer down, this does not sync the tail.
+        fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+            let this = self.get_mut();
+            ready!(this.poll_syncing(cx))?;
+            Pin::new(&mut this.writer).poll_shutdown(cx)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{FileMonitor, SyncPolicy};
+    use std::io::{Seek, SeekFrom, Write};
+
+    fn file(name: &str) -> std::fs::File {
+        std::fs::File::create(std::env::temp_dir().join(name)).unwrap()
+    }
+
+    #[test]
+    fn overwrites_are_synced_once() {
+        let mut fm = FileMonitor::new(file("write-monitor-overwrite"), SyncPolicy::default());
+        let monitor = fm.monitor();
+        fm.write_all(&[0; 100]).unwrap();
+        fm.seek(SeekFrom::Start(0)).unwrap();
+        fm.write_all(b"header").unwrap();
+        fm.sync().unwrap();
+        assert_eq!(monitor.bytes_written(), 106);
+        assert_eq!(monitor.bytes_flushed(), 100);
+        assert_eq!(monitor.bytes_synced(), 100);
+    }
+
+    #[test]
+    fn overwrites_count_towards_the_policy() {
+        let mut fm = FileMonitor::new(file("write-monitor-policy"), SyncPolicy::every_bytes(8));
+        let monitor = fm.monitor();
+        fm.write_all(&[0; 4]).unwrap();
+        fm.seek(SeekFrom::Start(0)).unwrap();
+        fm.write_all(&[0; 4]).unwrap();
+        assert_eq!(monitor.bytes_synced(), 0);
+        // The next write syncs the overwritten bytes first.
+        fm.write_all(&[0; 4]).unwrap();
+        assert_eq!(monitor.bytes_synced(), 4);
+    }
+
+    #[cfg(feature = "tokio-fs")]
+    #[test]
+    fn tokio_files_are_synced_by_policy() {
+        use super::TokioFileMonitor;
+        use tokio::io::AsyncWriteExt;
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let file = tokio::fs::File::from_std(file("write-monitor-tokio-sync"));
+            let mut fm = TokioFileMonitor::new(file, SyncPolicy::every_bytes(4))
+                .await
+                .unwrap();
+            let monitor = fm.monitor();
+            fm.write_all(b"hello").await.unwrap();
+            assert_eq!(monitor.bytes_synced(), 0);
+            // The next write syncs the first one before going through.
+            fm.write_all(b" world").await.unwrap();
+            assert_eq!(monitor.bytes_synced(), 5);
+            fm.finish().await.unwrap();
+            assert_eq!(monitor.bytes_synced(), 11);
+            assert!(monitor.is_finished());
+        });
+    }
+
+    #[test]
+    fn holes_hold_back_the_synced_prefix() {
+        let mut fm = FileMonitor::new(file("write-monitor-hole"), SyncPolicy::default());
+        let monitor = fm.monitor();
+        fm.seek(SeekFrom::Start(1000)).unwrap();
+        fm.write_all(&[0; 100]).unwrap();
+        fm.sync().unwrap();
+        assert_eq!(monitor.bytes_flushed(), 100);
+        assert_eq!(monitor.bytes_synced(), 0);
+        fm.seek(SeekFrom::Start(0)).unwrap();
+        fm.write_all(&[0; 600]).unwrap();
+        fm.sync().unwrap();
+        assert_eq!(monitor.bytes_synced(), 600);
+        fm.write_all(&[0; 400]).unwrap();
+        fm.sync().unwrap();
+        assert_eq!(monitor.bytes_synced(), 1100);
+    }
+
+    #[test]
+    fn rewinding_keeps_what_was_synced_before_the_offset() {
+        let mut fm = FileMonitor::new(file("write-monitor-rewind"), SyncPolicy::default());
+        let monitor = fm.monitor();
+        fm.write_all(&[0; 500]).unwrap();
+        fm.sync().unwrap();
+        fm.write_all(&[0; 500]).unwrap();
+        assert!(monitor.rewind_to(800));
+        assert_eq!(monitor.bytes_written(), 800);
+        assert_eq!(monitor.bytes_synced(), 500);
+        assert_eq!(monitor.bytes_flushed(), 500);
+        assert!(monitor.rewind_to(300));
+        assert_eq!(monitor.bytes_synced(), 300);
+    }
+
+    #[test]
+    fn resuming_at_an_offset_counts_it_as_synced() {
+        let wm = crate::WriteMonitor::with_offset(file("write-monitor-resume"), 300);
+        let monitor = wm.monitor();
+        assert_eq!(monitor.bytes_synced(), 300);
+        assert_eq!(monitor.bytes_flushed(), 300);
+    }
+}
@@ -0,0 +1,144 @@
+//! Human readable byte counts, rates and durations.
+use core::fmt;
+use core::time::Duration;
+
+/// The units to show byte counts in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum Units {
+    /// Powers of 1024: KiB, MiB, GiB, ...
+    #[default]
+    Iec,
+    /// Powers of 1000: kB, MB, GB, ...
+    Si,
+}
+
+impl Units {
+    fn base(self) -> f64 {
+        match self {
+            Self::Iec => 1024.0,
+            Self::Si => 1000.0,
+        }
+    }
+
+    fn prefixes(self) -> [&'static str; 6] {
+        match self {
+            Self::Iec => ["KiB", "MiB", "GiB", "TiB", "PiB", "EiB"],
+            Self::Si => ["kB", "MB", "GB", "TB", "PB", "EB"],
+        }
+    }
+
+    /// Write `value` bytes scaled to the largest fitting unit, with one decimal unless the formatter asks otherwise.
+    fn write(self, f: &mut fmt::Formatter<'_>, value: f64) -> fmt::Result {
+        let base = self.base();
+        if value < base {
+            return write!(f, "{value:.0} B");
+        }
+        let prefixes = self.prefixes();
+        let mut value = value / base;
+        let mut prefix = 0;
+        while value >= base && prefix < prefixes.len() - 1 {
+            value /= base;
+            prefix += 1;
+        }
+        let precision = f.precision().unwrap_or(1);
+        write!(f, "{value:.precision$} {}", prefixes[prefix])
+    }
+}
+
+/// Displays a byte count such as `12.3 MiB`.
+///
+/// The precision of the format string sets the number of decimals, one by default.
+/// ```
+/// use write_monitor::Bytes;
+/// assert_eq!(Bytes::new(512).to_string(), "512 B");
+/// assert_eq!(Bytes::new(12_900_000).to_string(), "12.3 MiB");
+/// assert_eq!(Bytes::new(12_900_000).si().to_string(), "12.9 MB");
+/// assert_eq!(format!("{:.2}", Bytes::new(1536)), "1.50 KiB");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Bytes {
+    pub bytes: u64,
+    pub units: Units,
+}
+
+impl Bytes {
+    /// `bytes` in [`Units::Iec`].
+    pub fn new(bytes: u64) -> Self {
+        Self {
+            bytes,
+            units: Units::Iec,
+        }
+    }
+
+    pub fn si(self) -> Self {
+        Self {
+            units: Units::Si,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for Bytes {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.units.write(f, self.bytes as f64)
+    }
+}
+
+/// Displays a rate such as `1.2 MiB/s`.
+/// ```
+/// use write_monitor::BytesPerSecond;
+/// assert_eq!(BytesPerSecond::new(2_500_000.0).si().to_string(), "2.5 MB/s");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BytesPerSecond {
+    pub bytes_per_second: f64,
+    pub units: Units,
+}
+
+impl BytesPerSecond {
+    /// `bytes_per_second` in [`Units::Iec`].
+    pub fn new(bytes_per_second: f64) -> Self {
+        Self {
+            bytes_per_second,
+            units: Units::Iec,
+        }
+    }
+
+    pub fn si(self) -> Self {
+        Self {
+            units: Units::Si,
+            ..self
+        }
+    }
+}
+
+impl fmt::Display for BytesPerSecond {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.units.write(f, self.bytes_per_second.max(0.0))?;
+        f.write_str("/s")
+    }
+}
+
+/// Displays a duration rounded to seconds, such as `1h02m03s`, `4m05s` or `6s`.
+/// ```
+/// use write_monitor::HumanDuration;
+/// use std::time::Duration;
+/// assert_eq!(HumanDuration(Duration::from_secs(3723)).to_string(), "1h02m03s");
+/// assert_eq!(HumanDuration(Duration::from_millis(5600)).to_string(), "6s");
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HumanDuration(pub Duration);
+
+impl fmt::Display for HumanDuration {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let secs = self.0.as_secs() + u64::from(self.0.subsec_millis() >= 500);
+        let (hours, minutes, secs) = (secs / 3600, secs / 60 % 60, secs % 60);
+        if hours > 0 {
+            write!(f, "{hours}h{minutes:02}m{secs:02}s")
+        } else if minutes > 0 {
+            write!(f, "{minutes}m{secs:02}s")
+        } else {
+            write!(f, "{secs}s")
+        }
+    }
+}
@@ -0,0 +1,369 @@
+//! Deciding how many bytes a write may pass to the inner writer, and waiting until it may.
+use crate::state::State;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use crate::timer::{Sleep, Timer};
+use alloc::vec::Vec;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use core::task::{Context, Poll};
+use std::io::{self, IoSlice};
+use std::time::{Duration, Instant};
+
+/// The outcome of asking to write some bytes.
+pub(crate) enum Admission {
+    /// Pass this many bytes to the inner writer.
+    Granted(usize),
+    /// Nothing may be written for this long, [`Duration::MAX`] until the state changes.
+    Retry(Duration),
+    /// The write fails with this error.
+    Rejected(io::Error),
+}
+
+impl State {
+    pub(crate) fn admit(&self, want: usize) -> Admission {
+        if let Some(error) = self.cancel.error() {
+            return Admission::Rejected(error);
+        }
+        if self.is_paused() {
+            return Admission::Retry(Duration::MAX);
+        }
+        let want = match self.reserve_quota(want) {
+            Ok(want) => want,
+            Err(error) => return Admission::Rejected(error),
+        };
+        match self.take_bandwidth(want as u64) {
+            Ok(granted) => {
+                self.quota.unreserve(want as u64 - granted);
+                Admission::Granted(granted as usize)
+            }
+            Err(wait) => {
+                self.quota.unreserve(want as u64);
+                Admission::Retry(wait)
+            }
+        }
+    }
+
+    /// Take up to `want` bytes from the rate limit and the bandwidth group.
+    fn take_bandwidth(&self, want: u64) -> Result<u64, Duration> {
+        // Reading the clock costs more than the rest of an unlimited write.
+        let granted = match self.limiter.is_enabled() {
+            true => self.limiter.take(want, self.meter.now())?,
+            false => want,
+        };
+        let Some(group) = self.bandwidth.get() else {
+            return Ok(granted);
+        };
+        match group.take(granted) {
+            Ok(shared) => {
+                self.limiter.release(granted - shared);
+                Ok(shared)
+            }
+            Err(wait) => {
+                self.limiter.release(granted);
+                Err(wait)
+            }
+        }
+    }
+
+    /// Block the thread until some of `want` bytes may be written.
+    pub(crate) fn admit_blocking(&self, want: usize) -> io::Result<usize> {
+        loop {
+            let generation = self.notifier.generation();
+            match self.admit(want) {
+                Admission::Granted(n) => return Ok(n),
+                Admission::Rejected(error) => return Err(error),
+                Admission::Retry(wait) => {
+                    self.notifier
+                        .block(generation, Instant::now().checked_add(wait));
+                }
+            }
+        }
+    }
+
+    /// Poll until some of `want` bytes may be written, sleeping on `delay` created by `timer`.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    pub(crate) fn poll_admit(
+        &self,
+        cx: &mut Context<'_>,
+        delay: &mut Option<Sleep>,
+        timer: &dyn Timer,
+        want: usize,
+    ) -> Poll<io::Result<usize>> {
+        loop {
+            let generation = self.notifier.generation();
+            match self.admit(want) {
+                Admission::Granted(n) => {
+                    *delay = None;
+                    return Poll::Ready(Ok(n));
+                }
+                Admission::Rejected(error) => {
+                    *delay = None;
+                    return Poll::Ready(Err(error));
+                }
+                Admission::Retry(wait) => {
+                    if wait != Duration::MAX {
+                        let sleep = delay.get_or_insert_with(|| timer.sleep(wait));
+                        if sleep.as_mut().poll(cx).is_ready() {
+                            *delay = None;
+                            continue;
+                        }
+                    }
+                    // Also wake up when the limits change.
+                    if self.notifier.register(cx.waker(), generation) {
+                        return Poll::Pending;
+                    }
+                }
+            }
+        }
+    }
+
+    /// Return what was granted but not written.
+    pub(crate) fn release(&self, granted: usize) {
+        self.quota.unreserve(granted as u64);
+        self.limiter.release(granted as u64);
+        if let Some(group) = self.bandwidth.get() {
+            group.release(granted as u64);
+        }
+    }
+}
+
+/// The leading `len` bytes of `bufs`.
+pub(crate) fn truncate<'a>(bufs: &'a [IoSlice<'a>], mut len: usize) -> Vec<IoSlice<'a>> {
+    let mut out = Vec::new();
+    for buf in bufs {
+        if len == 0 {
+            break;
+        }
+        let n = buf.len().min(len);
+        out.push(IoSlice::new(&buf[..n]));
+        len -= n;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::truncate;
+    use crate::{QuotaPolicy, WriteMonitor};
+    use std::io::{IoSlice, Write};
+
+    fn bufs() -> [IoSlice<'static>; 3] {
+        [
+            IoSlice::new(b"abc"),
+            IoSlice::new(b"defg"),
+            IoSlice::new(b"hi"),
+        ]
+    }
+
+    #[test]
+    fn truncate_keeps_the_leading_bytes() {
+        let bufs = bufs();
+        let cut = |len| {
+            truncate(&bufs, len)
+                .iter()
+                .map(|buf| buf.to_vec())
+                .collect::<Vec<_>>()
+        };
+        assert!(cut(0).is_empty());
+        assert_eq!(cut(2), [b"ab".to_vec()]);
+        assert_eq!(cut(5), [b"abc".to_vec(), b"de".to_vec()]);
+        assert_eq!(cut(9), [b"abc".to_vec(), b"defg".to_vec(), b"hi".to_vec()]);
+        assert_eq!(cut(100).len(), 3);
+    }
+
+    #[test]
+    fn vectored_write_is_truncated_to_the_limit() {
+        let mut wm = WriteMonitor::with_limit(Vec::new(), 5, QuotaPolicy::Truncate);
+        assert_eq!(wm.write_vectored(&bufs()).unwrap(), 5);
+        assert_eq!(wm.get_ref(), b"abcde");
+        assert_eq!(wm.bytes_written(), 5);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_vectored_write_is_truncated_to_the_limit() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut wm = WriteMonitor::with_limit(Vec::new(), 5, QuotaPolicy::Truncate);
+        let bufs = bufs();
+        let write = tokio::io::AsyncWriteExt::write_vectored(&mut wm, &bufs);
+        let n = rt.block_on(write).unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(wm.get_ref(), b"abcde");
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn futures_vectored_write_is_truncated_to_the_limit() {
+        let mut wm = WriteMonitor::with_limit(Vec::new(), 5, QuotaPolicy::Truncate);
+        let bufs = bufs();
+        let write = futures::io::AsyncWriteExt::write_vectored(&mut wm, &bufs);
+        let n = futures::executor::block_on(write).unwrap();
+        assert_eq!(n, 5);
+        assert_eq!(wm.get_ref(), b"abcde");
+    }
+
+    /// Run `write` while `monitor` is paused, and `then` from another thread after a while.
+    fn paused_then<T>(
+        monitor: crate::Monitor,
+        then: impl FnOnce(&crate::Monitor) + Send + 'static,
+        write: impl FnOnce() -> T,
+    ) -> (T, std::time::Duration) {
+        monitor.pause();
+        let start = std::time::Instant::now();
+        let other = std::thread::spawn(move || {
+            std::thread::sleep(std::time::Duration::from_millis(100));
+            then(&monitor);
+        });
+        let r = write();
+        other.join().unwrap();
+        (r, start.elapsed())
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_write_waits_for_resume() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut wm = WriteMonitor::new(Vec::new());
+        let (r, waited) = paused_then(
+            wm.monitor(),
+            |m| m.resume(),
+            || rt.block_on(tokio::io::AsyncWriteExt::write_all(&mut wm, b"hello")),
+        );
+        r.unwrap();
+        assert!(waited >= std::time::Duration::from_millis(100));
+        assert_eq!(wm.get_ref(), b"hello");
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_paused_write_fails_on_cancel() {
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let mut wm = WriteMonitor::new(Vec::new());
+        let (r, _) = paused_then(
+            wm.monitor(),
+            |m| m.cancel(),
+            || rt.block_on(tokio::io::AsyncWriteExt::write_all(&mut wm, b"hello")),
+        );
+        assert!(crate::Cancelled::find(&r.unwrap_err()).is_some());
+        assert!(wm.get_ref().is_empty());
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn futures_write_waits_for_resume() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let (r, waited) = paused_then(
+            wm.monitor(),
+            |m| m.resume(),
+            || {
+                futures::executor::block_on(futures::io::AsyncWriteExt::write_all(
+                    &mut wm, b"hello",
+                ))
+            },
+        );
+        r.unwrap();
+        assert!(waited >= std::time::Duration::from_millis(100));
+        assert_eq!(wm.get_ref(), b"hello");
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn futures_paused_write_fails_on_cancel() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let (r, _) = paused_then(
+            wm.monitor(),
+            |m| m.cancel(),
+            || {
+                futures::executor::block_on(futures::io::AsyncWriteExt::write_all(
+                    &mut wm, b"hello",
+                ))
+            },
+        );
+        assert!(crate::Cancelled::find(&r.unwrap_err()).is_some());
+        assert!(wm.get_ref().is_empty());
+    }
+
+    #[test]
+    fn blocking_write_waits_for_resume() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let (r, waited) = paused_then(wm.monitor(), |m| m.resume(), || wm.write_all(b"hello"));
+        r.unwrap();
+        assert!(waited >= std::time::Duration::from_millis(100));
+        assert_eq!(wm.get_ref(), b"hello");
+    }
+
+    /// A writer that takes a while to accept anything, counting what it accepts.
+    #[derive(Clone, Default)]
+    struct Slow(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl Write for Slow {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            self.0
+                .fetch_add(buf.len(), std::sync::atomic::Ordering::SeqCst);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn concurrent_writes_share_the_quota() {
+        let slow = Slow::default();
+        let wm = WriteMonitor::with_limit(slow.clone(), 5, QuotaPolicy::Reject);
+        let barrier = std::sync::Barrier::new(4);
+        let accepted = std::thread::scope(|s| {
+            let writers: Vec<_> = (0..4)
+                .map(|_| {
+                    let mut wm = wm.clone_shared();
+                    let barrier = &barrier;
+                    s.spawn(move || {
+                        barrier.wait();
+                        wm.write(b"hello").is_ok()
+                    })
+                })
+                .collect();
+            writers
+                .into_iter()
+                .map(|w| w.join().unwrap())
+                .filter(|&ok| ok)
+                .count()
+        });
+        assert_eq!(accepted, 1);
+        assert_eq!(slow.0.load(std::sync::atomic::Ordering::SeqCst), 5);
+        assert_eq!(wm.monitor().quota_remaining(), Some(0));
+    }
+
+    /// A clock that counts how often it is read.
+    #[derive(Clone, Default)]
+    struct Counting(std::sync::Arc<std::sync::atomic::AtomicUsize>);
+
+    impl crate::Clock for Counting {
+        fn now(&self) -> std::time::Duration {
+            self.0.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            std::time::Duration::ZERO
+        }
+    }
+
+    #[test]
+    fn unlimited_writes_do_not_read_the_clock() {
+        let clock = Counting::default();
+        let mut wm = WriteMonitor::with_clock(std::io::sink(), clock.clone());
+        wm.write_all(b"a").unwrap();
+        let reads = clock.0.load(std::sync::atomic::Ordering::SeqCst);
+        for _ in 0..100 {
+            wm.write_all(b"a").unwrap();
+        }
+        assert_eq!(clock.0.load(std::sync::atomic::Ordering::SeqCst), reads);
+    }
+}
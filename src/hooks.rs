@@ -0,0 +1,453 @@
+//! Callbacks that fire as a transfer makes progress.
+use crate::{state::State, Progress, WriteMonitor};
+use alloc::boxed::Box;
+use alloc::vec::Vec;
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::io::ErrorKind;
+use std::sync::{Mutex, MutexGuard, TryLockError};
+use std::time::Duration;
+
+/// [`WriteMonitor::on_bytes`] callbacks fire at most this often unless changed with [`WriteMonitor::set_callback_interval`].
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(100);
+
+type Callback = Box<dyn FnMut(&Progress) + Send>;
+type ErrorCallback = Box<dyn FnMut(ErrorKind) + Send>;
+
+struct Every {
+    step: u64,
+    next: u64,
+    f: Callback,
+}
+
+impl Every {
+    /// The first multiple of the step after `bytes`.
+    fn after(step: u64, bytes: u64) -> u64 {
+        (bytes / step).saturating_add(1).saturating_mul(step)
+    }
+}
+
+struct Milestones {
+    /// Percent between milestones.
+    step: u64,
+    /// Milestones passed so far.
+    passed: u64,
+    f: Callback,
+}
+
+impl Milestones {
+    fn passed(&self, unique: u64, total: Option<u64>) -> u64 {
+        let percent = match total {
+            None => return 0,
+            Some(0) => 100,
+            Some(total) => (u128::from(unique) * 100 / u128::from(total)).min(100) as u64,
+        };
+        percent / self.step
+    }
+
+    /// Bytes at which the next milestone may be passed.
+    fn next(&self, total: Option<u64>) -> u64 {
+        let percent = (self.passed + 1) * self.step;
+        match total {
+            Some(total) if percent <= 100 => (u128::from(total) * u128::from(percent) / 100) as u64,
+            _ => u64::MAX,
+        }
+    }
+}
+
+/// The progress callbacks, which fire while it is locked.
+#[derive(Default)]
+struct Registry {
+    every: Vec<Every>,
+    milestones: Vec<Milestones>,
+}
+
+impl Registry {
+    fn next(&self, total: Option<u64>) -> u64 {
+        let every = self.every.iter().map(|every| every.next);
+        every
+            .min()
+            .unwrap_or(u64::MAX)
+            .min(self.next_milestone(total))
+    }
+
+    fn next_milestone(&self, total: Option<u64>) -> u64 {
+        let milestones = self.milestones.iter().map(|m| m.next(total));
+        milestones.min().unwrap_or(u64::MAX)
+    }
+}
+
+/// The callbacks of a [`State`], checked with a couple of atomics on the hot path.
+pub(crate) struct Hooks {
+    armed: AtomicBool,
+    /// Bytes written at which a progress callback may be due.
+    next: AtomicU64,
+    /// Bytes written at which a milestone may be passed, these are not held back by `quiet_until`.
+    next_milestone: AtomicU64,
+    /// Clock reading in nanoseconds before which no [`Every`] callback fires.
+    quiet_until: AtomicU64,
+    /// Minimum time in nanoseconds between two [`Every`] callbacks.
+    interval: AtomicU64,
+    completed: AtomicBool,
+    registry: Mutex<Registry>,
+    complete: Mutex<Vec<Callback>>,
+    error: Mutex<Vec<ErrorCallback>>,
+}
+
+impl Default for Hooks {
+    fn default() -> Self {
+        Self {
+            armed: AtomicBool::new(false),
+            next: AtomicU64::new(0),
+            next_milestone: AtomicU64::new(0),
+            quiet_until: AtomicU64::new(0),
+            interval: AtomicU64::new(DEFAULT_INTERVAL.as_nanos() as u64),
+            completed: AtomicBool::new(false),
+            registry: Default::default(),
+            complete: Default::default(),
+            error: Default::default(),
+        }
+    }
+}
+
+impl core::fmt::Debug for Hooks {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Hooks")
+            .field("armed", &self.armed)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Hooks {
+    fn lock(&self) -> MutexGuard<'_, Registry> {
+        lock(&self.registry)
+    }
+
+    /// Add `f` to `list` of callbacks that only fire on completion or errors.
+    fn push<F: ?Sized>(&self, list: &Mutex<Vec<Box<F>>>, f: Box<F>) {
+        lock(list).push(f);
+        self.armed.store(true, Ordering::Release);
+    }
+
+    fn register(&self, total: Option<u64>, f: impl FnOnce(&mut Registry)) {
+        let mut registry = self.lock();
+        f(&mut registry);
+        self.store_next(&registry, total);
+        self.armed.store(true, Ordering::Release);
+    }
+
+    /// Check the progress callbacks again on the next write, the total changed.
+    pub(crate) fn rearm(&self) {
+        self.next.store(0, Ordering::Release);
+        self.next_milestone.store(0, Ordering::Release);
+    }
+
+    /// The counters moved to `bytes` written, `unique` of them distinct, without anything being written.
+    ///
+    /// Progress callbacks fire again from there on, whether the counters went back or forward.
+    pub(crate) fn rebase(&self, bytes: u64, unique: u64, total: Option<u64>) {
+        if !self.armed.load(Ordering::Acquire) {
+            return;
+        }
+        let mut registry = self.lock();
+        for every in &mut registry.every {
+            every.next = Every::after(every.step, bytes);
+        }
+        for milestones in &mut registry.milestones {
+            milestones.passed = milestones.passed(unique, total);
+        }
+        self.store_next(&registry, total);
+    }
+
+    fn store_next(&self, registry: &Registry, total: Option<u64>) {
+        self.next.store(registry.next(total), Ordering::Release);
+        let next_milestone = registry.next_milestone(total);
+        self.next_milestone.store(next_milestone, Ordering::Release);
+    }
+}
+
+fn lock<T>(mutex: &Mutex<T>) -> MutexGuard<'_, T> {
+    mutex.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+/// Call every callback in `list` without holding its lock, so they may use a writer sharing the state.
+///
+/// Callbacks are taken out while they run, a thread firing the same list meanwhile skips them.
+fn run<F: ?Sized>(list: &Mutex<Vec<Box<F>>>, call: impl FnMut(&mut Box<F>)) {
+    let mut taken = core::mem::take(&mut *lock(list));
+    taken.iter_mut().for_each(call);
+    let mut list = lock(list);
+    taken.append(&mut list);
+    *list = taken;
+}
+
+impl State {
+    /// Fire the progress and completion callbacks that are due.
+    pub(crate) fn run_hooks(&self) {
+        if !self.hooks.armed.load(Ordering::Acquire) {
+            return;
+        }
+        if self.bytes_written() >= self.hooks.next.load(Ordering::Acquire) {
+            self.run_progress_hooks();
+        }
+        if self.is_complete() {
+            self.run_complete_hooks();
+        }
+    }
+
+    fn run_progress_hooks(&self) {
+        let now = self.meter.now().as_nanos() as u64;
+        let quiet = now < self.hooks.quiet_until.load(Ordering::Acquire);
+        if quiet && self.bytes_written() < self.hooks.next_milestone.load(Ordering::Acquire) {
+            return;
+        }
+        // Someone else is firing, they will pick up these bytes on the next write.
+        let mut registry = match self.hooks.registry.try_lock() {
+            Ok(registry) => registry,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => return,
+        };
+        let progress = self.progress();
+        let unique = self.unique_bytes_written();
+        let mut fired = false;
+        for every in registry.every.iter_mut().filter(|_| !quiet) {
+            if progress.bytes >= every.next {
+                every.next = Every::after(every.step, progress.bytes);
+                (every.f)(&progress);
+                fired = true;
+            }
+        }
+        for milestones in &mut registry.milestones {
+            let passed = milestones.passed(unique, progress.total);
+            if passed > milestones.passed {
+                milestones.passed = passed;
+                (milestones.f)(&progress);
+            }
+        }
+        if fired {
+            let interval = self.hooks.interval.load(Ordering::Acquire);
+            let quiet_until = now.saturating_add(interval);
+            self.hooks.quiet_until.store(quiet_until, Ordering::Release);
+        }
+        self.hooks.store_next(&registry, progress.total);
+    }
+
+    /// Fire the completion callbacks, once.
+    pub(crate) fn run_complete_hooks(&self) {
+        if !self.hooks.armed.load(Ordering::Acquire)
+            || self.hooks.completed.swap(true, Ordering::AcqRel)
+        {
+            return;
+        }
+        let progress = self.progress();
+        run(&self.hooks.complete, |f| f(&progress));
+    }
+
+    pub(crate) fn run_error_hooks(&self, kind: ErrorKind) {
+        if !self.hooks.armed.load(Ordering::Acquire) {
+            return;
+        }
+        run(&self.hooks.error, |f| f(kind));
+    }
+}
+
+impl<W> WriteMonitor<W> {
+    /// Call `f` every time another `bytes` bytes have been written.
+    ///
+    /// Progress callbacks run on the writing thread, at most once per [`WriteMonitor::set_callback_interval`].
+    /// Thresholds passed in between are reported by one call on a later write.
+    /// They may write to a writer sharing the monitor, but must not register callbacks
+    /// or move the counters with [`Monitor::set`](crate::Monitor::set), which would deadlock.
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// let mut wm = WriteMonitor::new(Vec::new());
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let log = seen.clone();
+    /// wm.set_callback_interval(Duration::ZERO);
+    /// wm.on_bytes(4, move |progress| log.lock().unwrap().push(progress.bytes));
+    /// for _ in 0..5 {
+    ///     wm.write_all(b"ab").unwrap();
+    /// }
+    /// assert_eq!(*seen.lock().unwrap(), [4, 8]);
+    /// ```
+    pub fn on_bytes(&self, bytes: u64, f: impl FnMut(&Progress) + Send + 'static) {
+        let step = bytes.max(1);
+        let next = Every::after(step, self.state.bytes_written());
+        let f = Box::new(f);
+        self.state.hooks.register(self.state.total(), |registry| {
+            registry.every.push(Every { step, next, f })
+        });
+    }
+
+    /// Call `f` each time the written fraction of the total passes another multiple of `percent` percent.
+    ///
+    /// Nothing fires while the total is unknown.
+    /// Unlike [`WriteMonitor::on_bytes`] this is not held back by the callback interval,
+    /// a write that passes several milestones at once is reported by one call.
+    /// The callback has the same restrictions as those of [`WriteMonitor::on_bytes`].
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// use std::sync::{Arc, Mutex};
+    /// let mut wm = WriteMonitor::with_total(Vec::new(), 100);
+    /// let seen = Arc::new(Mutex::new(Vec::new()));
+    /// let log = seen.clone();
+    /// wm.on_percent(25, move |progress| log.lock().unwrap().push(progress.bytes));
+    /// for _ in 0..4 {
+    ///     wm.write_all(&[0; 25]).unwrap();
+    /// }
+    /// assert_eq!(*seen.lock().unwrap(), [25, 50, 75, 100]);
+    /// ```
+    pub fn on_percent(&self, percent: u8, f: impl FnMut(&Progress) + Send + 'static) {
+        let mut milestones = Milestones {
+            step: u64::from(percent.clamp(1, 100)),
+            passed: 0,
+            f: Box::new(f),
+        };
+        milestones.passed =
+            milestones.passed(self.state.unique_bytes_written(), self.state.total());
+        self.state.hooks.register(self.state.total(), |registry| {
+            registry.milestones.push(milestones)
+        });
+    }
+
+    /// Call `f` once, when the total is reached or the writer is shut down or closed successfully.
+    ///
+    /// A writer that is dropped right after a successful flush is closed as well, see [`Lifecycle::Closed`](crate::Lifecycle::Closed).
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// use std::sync::atomic::{AtomicBool, Ordering};
+    /// use std::sync::Arc;
+    /// let mut wm = WriteMonitor::new(Vec::new());
+    /// let done = Arc::new(AtomicBool::new(false));
+    /// let flag = done.clone();
+    /// wm.on_complete(move |_| flag.store(true, Ordering::Release));
+    /// wm.write_all(b"hello").unwrap();
+    /// wm.flush().unwrap();
+    /// assert!(!done.load(Ordering::Acquire));
+    /// drop(wm);
+    /// assert!(done.load(Ordering::Acquire));
+    /// ```
+    pub fn on_complete(&self, f: impl FnMut(&Progress) + Send + 'static) {
+        let hooks = &self.state.hooks;
+        hooks.push(&hooks.complete, Box::new(f));
+    }
+
+    /// Call `f` with the kind of every error returned by the inner writer.
+    ///
+    /// [`ErrorKind::WouldBlock`] and [`ErrorKind::Interrupted`] are retried by callers and do not count.
+    pub fn on_error(&self, f: impl FnMut(ErrorKind) + Send + 'static) {
+        let hooks = &self.state.hooks;
+        hooks.push(&hooks.error, Box::new(f));
+    }
+
+    /// Set the minimum time between two [`WriteMonitor::on_bytes`] callbacks, 100ms by default.
+    ///
+    /// Milestone, completion and error callbacks are not held back.
+    pub fn set_callback_interval(&self, interval: Duration) {
+        let interval = interval.as_nanos().min(u128::from(u64::MAX)) as u64;
+        self.state.hooks.interval.store(interval, Ordering::Release);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ManualClock, WriteMonitor};
+    use std::collections::VecDeque;
+    use std::io::{self, ErrorKind, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    /// A writer that fails with the queued kinds in turn, then accepts everything.
+    #[derive(Clone, Default)]
+    struct Failing(Arc<Mutex<VecDeque<ErrorKind>>>);
+
+    impl Write for Failing {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            match self.0.lock().unwrap().pop_front() {
+                Some(kind) => Err(kind.into()),
+                None => Ok(buf.len()),
+            }
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn error_callbacks_see_the_errors_that_count() {
+        let failing = Failing::default();
+        let mut wm = WriteMonitor::new(failing.clone());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        let mut shared = wm.clone_shared();
+        // Writing from the callback to a writer sharing the state must not deadlock.
+        wm.on_error(move |kind| {
+            log.lock().unwrap().push(kind);
+            shared.write_all(b"retry").unwrap();
+        });
+        failing.0.lock().unwrap().extend([
+            ErrorKind::WouldBlock,
+            ErrorKind::Interrupted,
+            ErrorKind::BrokenPipe,
+        ]);
+        assert!(wm.write(b"a").is_err());
+        assert!(wm.write_all(b"a").is_err());
+        assert_eq!(*seen.lock().unwrap(), [ErrorKind::BrokenPipe]);
+        assert_eq!(wm.bytes_written(), 5);
+    }
+
+    #[test]
+    fn complete_callbacks_may_write_to_a_shared_writer() {
+        let mut wm = WriteMonitor::with_total(Vec::new(), 5);
+        let mut shared = wm.clone_shared();
+        wm.on_complete(move |_| shared.write_all(b"!").unwrap());
+        wm.write_all(b"hello").unwrap();
+        assert_eq!(wm.bytes_written(), 6);
+    }
+
+    #[test]
+    fn callback_interval_holds_back_progress_callbacks() {
+        let clock = ManualClock::new();
+        let mut wm = WriteMonitor::with_clock(Vec::new(), clock.clone());
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let log = seen.clone();
+        wm.set_callback_interval(Duration::from_secs(1));
+        wm.on_bytes(10, move |p| log.lock().unwrap().push(p.bytes));
+        wm.write_all(&[0; 10]).unwrap();
+        wm.write_all(&[0; 10]).unwrap();
+        clock.advance(Duration::from_millis(999));
+        wm.write_all(&[0; 10]).unwrap();
+        assert_eq!(*seen.lock().unwrap(), [10]);
+        clock.advance(Duration::from_millis(1));
+        wm.write_all(&[0; 10]).unwrap();
+        assert_eq!(*seen.lock().unwrap(), [10, 40]);
+    }
+
+    #[test]
+    fn callbacks_fire_again_after_rewinding() {
+        let mut wm = WriteMonitor::with_total(Vec::new(), 1000);
+        let monitor = wm.monitor();
+        let seen = Arc::new(Mutex::new(Vec::new()));
+        let (bytes, percent) = (seen.clone(), seen.clone());
+        wm.set_callback_interval(Duration::ZERO);
+        wm.on_bytes(100, move |p| bytes.lock().unwrap().push(("bytes", p.bytes)));
+        wm.on_percent(50, move |p| {
+            percent.lock().unwrap().push(("percent", p.bytes))
+        });
+        wm.write_all(&[0; 1000]).unwrap();
+        assert_eq!(*seen.lock().unwrap(), [("bytes", 1000), ("percent", 1000)]);
+        seen.lock().unwrap().clear();
+        assert!(monitor.rewind_to(0));
+        for _ in 0..5 {
+            wm.write_all(&[0; 100]).unwrap();
+        }
+        let bytes = [100, 200, 300, 400, 500].map(|n| ("bytes", n));
+        assert_eq!(seen.lock().unwrap()[..5], bytes);
+        assert_eq!(seen.lock().unwrap()[5..], [("percent", 500)]);
+    }
+}
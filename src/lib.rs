@@ -5,70 +5,539 @@
 //! ```
 //! use write_monitor::WriteMonitor;
 //! use std::io::Write;
-//! let mut buf = std::fs::File::create("somefile").unwrap();
-//! let mut wm = WriteMonitor::new(buf);
+//! let buf = std::fs::File::create(std::env::temp_dir().join("write-monitor-example")).unwrap();
 //! let big_data = std::fs::read("Cargo.toml").unwrap();
-//! let big_data_len = big_data.len();
+//! let mut wm = WriteMonitor::with_total(buf, big_data.len() as u64);
 //! let monitor = wm.monitor();
-//! std::thread::spawn(move || {
-//!     wm.write_all(&big_data).unwrap();
-//! });
-//! let mut last_written = 0;
-//! while monitor.bytes_written() < big_data_len as u64 {
-//!    let written = monitor.bytes_written();
-//!    if written != last_written {
-//!    println!("{} bytes written", written);
-//!    last_written = written;
-//!    }
-//!  std::thread::sleep(std::time::Duration::from_millis(100));
+//! let writer = std::thread::spawn(move || wm.write_all(&big_data));
+//! while !monitor.is_finished() {
+//!     monitor.blocking_changed();
+//!     println!("{} bytes written", monitor.bytes_written());
 //! }
+//! writer.join().unwrap().unwrap();
 //! ```
 
 extern crate alloc;
+#[cfg(feature = "std")]
+mod aggregate;
+#[cfg(feature = "std")]
+mod bandwidth;
+#[cfg(feature = "indicatif")]
+mod bar;
+#[cfg(feature = "std")]
+mod cancel;
+#[cfg(feature = "std")]
+mod errors;
+#[cfg(feature = "std")]
+mod extent;
+#[cfg(feature = "std")]
+mod file;
+mod format;
+#[cfg(feature = "std")]
+mod gate;
+#[cfg(feature = "std")]
+mod hooks;
+#[cfg(feature = "std")]
+mod lifecycle;
+#[cfg(feature = "std")]
+mod limit;
+#[cfg(feature = "std")]
+mod progress;
+#[cfg(feature = "std")]
+mod quota;
+#[cfg(feature = "std")]
+mod rate;
+mod read;
+#[cfg(feature = "std")]
+mod render;
+mod state;
+#[cfg(feature = "stats")]
+mod stats;
+#[cfg(any(feature = "futures", feature = "tokio"))]
+mod stream;
+#[cfg(feature = "std")]
+mod timer;
+#[cfg(feature = "std")]
+mod wait;
+
+#[cfg(feature = "std")]
+pub use aggregate::MonitorGroup;
+#[cfg(feature = "std")]
+pub use bandwidth::BandwidthGroup;
+#[cfg(feature = "std")]
+pub use cancel::Cancelled;
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio-fs")))]
+#[cfg(feature = "tokio-fs")]
+pub use file::TokioFileMonitor;
+#[cfg(feature = "std")]
+pub use file::{FileMonitor, SyncPolicy};
+pub use format::{Bytes, BytesPerSecond, HumanDuration, Units};
+#[cfg(feature = "std")]
+pub use lifecycle::Lifecycle;
+#[cfg(feature = "std")]
+pub use limit::RateLimit;
+#[cfg(feature = "std")]
+pub use progress::Progress;
+#[cfg(feature = "std")]
+pub use quota::{QuotaExceeded, QuotaPolicy};
+#[cfg(feature = "std")]
+pub use rate::{Clock, ManualClock, SystemClock};
+pub use read::ReadMonitor;
+#[cfg(feature = "std")]
+pub use render::Renderer;
+#[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+#[cfg(feature = "stats")]
+pub use stats::WriteStats;
+#[cfg(feature = "futures")]
+pub use stream::ProgressStream;
+#[cfg(feature = "tokio")]
+pub use stream::TokioProgressStream;
+#[cfg(feature = "tokio")]
+pub use timer::TokioTimer;
+#[cfg(feature = "std")]
+pub use timer::{Sleep, ThreadTimer, Timer};
+
 use alloc::sync::Arc;
 use core::sync::atomic::{AtomicU64, Ordering};
+use state::{Handle, State};
 
 #[cfg(any(feature = "futures", feature = "tokio"))]
 use core::{pin::Pin, task::Poll};
 
 #[cfg_attr(any(feature = "futures", feature = "tokio"), pin_project::pin_project)]
-#[derive(Debug, Clone)]
 pub struct WriteMonitor<W> {
     #[cfg_attr(any(feature = "futures", feature = "tokio"), pin)]
     inner: W,
-    bytes_written: Arc<AtomicU64>,
+    state: Handle,
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    timer: Option<Arc<dyn Timer>>,
+    /// The pending wait of an async write that is held back.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    delay: Option<timer::Sleep>,
+}
+
+impl<W: core::fmt::Debug> core::fmt::Debug for WriteMonitor<W> {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("WriteMonitor")
+            .field("inner", &self.inner)
+            .field("state", &self.state)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Cloning a `WriteMonitor` is the same as [`WriteMonitor::fork`],
+/// use [`WriteMonitor::clone_shared`] to keep counting into the same [`Monitor`].
+impl<W: Clone> Clone for WriteMonitor<W> {
+    fn clone(&self) -> Self {
+        self.fork()
+    }
+}
+
+impl<W: Clone> WriteMonitor<W> {
+    /// Clone the inner writer with a counter of its own.
+    ///
+    /// The fork starts from zero with the same total, clock and limits, and hands out a different [`Monitor`].
+    /// Callbacks and bandwidth groups are not carried over.
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// let mut first = WriteMonitor::new(Vec::new());
+    /// let mut second = first.fork();
+    /// first.write_all(b"hello").unwrap();
+    /// second.write_all(b"hi").unwrap();
+    /// assert_eq!(first.monitor().bytes_written(), 5);
+    /// assert_eq!(second.monitor().bytes_written(), 2);
+    /// ```
+    pub fn fork(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: Handle::new(self.state.fork()),
+            #[cfg(any(feature = "futures", feature = "tokio"))]
+            timer: self.timer.clone(),
+            #[cfg(any(feature = "futures", feature = "tokio"))]
+            delay: None,
+        }
+    }
+
+    /// Clone the inner writer, counting into the same [`Monitor`].
+    ///
+    /// The monitor is finished once every writer sharing it is dropped or closed.
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// let mut first = WriteMonitor::new(Vec::new());
+    /// let mut second = first.clone_shared();
+    /// first.write_all(b"hello").unwrap();
+    /// second.write_all(b"hi").unwrap();
+    /// assert_eq!(first.monitor().bytes_written(), 7);
+    /// drop(first);
+    /// assert!(!second.monitor().is_finished());
+    /// ```
+    pub fn clone_shared(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+            #[cfg(any(feature = "futures", feature = "tokio"))]
+            timer: self.timer.clone(),
+            #[cfg(any(feature = "futures", feature = "tokio"))]
+            delay: None,
+        }
+    }
 }
 
 impl<W> WriteMonitor<W> {
     pub fn new(inner: W) -> Self {
+        Self::from_handle(inner, Handle::new(State::new()))
+    }
+
+    fn from_handle(inner: W, state: Handle) -> Self {
         Self {
             inner,
-            bytes_written: Arc::new(AtomicU64::new(0)),
+            state,
+            #[cfg(any(feature = "futures", feature = "tokio"))]
+            timer: None,
+            #[cfg(any(feature = "futures", feature = "tokio"))]
+            delay: None,
         }
     }
+
+    /// Create a `WriteMonitor` that counts into the same state as `monitor`, like [`WriteMonitor::clone_shared`].
+    ///
+    /// Use this to monitor several writers, for example parts of a multipart upload, as one.
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// let mut first = WriteMonitor::new(Vec::new());
+    /// let mut second = WriteMonitor::with_monitor(std::io::sink(), &first.monitor());
+    /// first.write_all(b"hello").unwrap();
+    /// second.write_all(b"hi").unwrap();
+    /// assert_eq!(first.monitor().bytes_written(), 7);
+    /// ```
+    pub fn with_monitor(inner: W, monitor: &Monitor) -> Self {
+        Self::from_handle(inner, Handle::attach(monitor.state.clone()))
+    }
+
+    /// Rebuild a `WriteMonitor` from the parts returned by [`WriteMonitor::into_parts`] to resume monitoring.
+    ///
+    /// Any other `monitor` is attached to like [`WriteMonitor::with_monitor`].
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::Write;
+    /// let mut wm = WriteMonitor::new(Vec::new());
+    /// wm.write_all(b"hello").unwrap();
+    /// let (buf, monitor) = wm.into_parts();
+    /// assert!(!monitor.is_finished());
+    /// let mut wm = WriteMonitor::from_parts(buf, &monitor);
+    /// wm.write_all(b" world").unwrap();
+    /// assert_eq!(monitor.bytes_written(), 11);
+    /// assert_eq!(wm.into_inner(), b"hello world");
+    /// assert!(monitor.is_finished());
+    /// ```
+    pub fn from_parts(inner: W, monitor: &Monitor) -> Self {
+        Self::from_handle(inner, Handle::unpark(monitor.state.clone()))
+    }
+
+    /// Create a `WriteMonitor` for a transfer resumed at `offset`, which counts as already written.
+    pub fn with_offset(inner: W, offset: u64) -> Self {
+        let this = Self::new(inner);
+        this.state.resume_at(offset);
+        this
+    }
+
+    /// Create a `WriteMonitor` that expects `total` bytes to be written.
+    pub fn with_total(inner: W, total: u64) -> Self {
+        let this = Self::new(inner);
+        this.set_total(Some(total));
+        this
+    }
+
+    /// Create a `WriteMonitor` that measures rates and elapsed time with `clock`.
+    #[cfg(feature = "std")]
+    pub fn with_clock(inner: W, clock: impl Clock + 'static) -> Self {
+        Self::from_handle(inner, Handle::new(State::with_clock(Arc::new(clock))))
+    }
+
     pub fn bytes_written(&self) -> u64 {
-        self.bytes_written.load(Ordering::Acquire)
+        self.state.bytes_written()
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Writing to the inner writer directly is not monitored.
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Writing to the inner writer directly is not monitored.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    pub fn get_pin_mut(self: Pin<&mut Self>) -> Pin<&mut W> {
+        self.project().inner
+    }
+
+    /// Take the inner writer back, this finishes the [`Monitor`] like dropping the `WriteMonitor` does.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+
+    /// Take the inner writer back without finishing the [`Monitor`],
+    /// pass both to [`WriteMonitor::from_parts`] to resume monitoring.
+    pub fn into_parts(self) -> (W, Monitor) {
+        let monitor = Monitor::new(self.state.park());
+        (self.inner, monitor)
+    }
+
+    /// Set or clear the number of bytes expected to be written.
+    pub fn set_total(&self, total: Option<u64>) {
+        self.state.set_total(total)
+    }
+
+    /// Limit the bandwidth of this writer, it can be changed later through any [`Monitor`].
+    ///
+    /// Writes block (std) or wait on a [`Timer`] (tokio, futures) until the limit lets them through,
+    /// and may write fewer bytes than they were given.
+    #[cfg(feature = "std")]
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.state.set_rate_limit(limit)
+    }
+
+    /// Use `timer` to wait in the async write paths instead of the default [`TokioTimer`] or [`ThreadTimer`].
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    pub fn set_timer(&mut self, timer: impl Timer + 'static) {
+        self.timer = Some(Arc::new(timer));
+        self.delay = None;
     }
 
     pub fn monitor(&self) -> Monitor {
-        Monitor {
-            bytes_written: self.bytes_written.clone(),
-        }
+        Monitor::new(self.state.shared())
     }
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 pub struct Monitor {
-    bytes_written: Arc<AtomicU64>,
+    state: Arc<State>,
+    /// The last change generation observed through this handle.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    seen: AtomicU64,
+}
+
+impl Clone for Monitor {
+    fn clone(&self) -> Self {
+        Self {
+            state: self.state.clone(),
+            seen: AtomicU64::new(self.seen.load(Ordering::Acquire)),
+        }
+    }
 }
 
 impl Monitor {
+    pub(crate) fn new(state: Arc<State>) -> Self {
+        Self {
+            state,
+            seen: AtomicU64::new(0),
+        }
+    }
+
     pub fn bytes_written(&self) -> u64 {
-        self.bytes_written.load(Ordering::Acquire)
+        self.state.bytes_written()
+    }
+
+    /// Bytes confirmed by a successful flush, shutdown or close of the writer.
+    ///
+    /// [`Monitor::bytes_written`] counts bytes as soon as the inner writer accepts them,
+    /// which may still be sitting in a buffer.
+    /// Like [`Monitor::unique_bytes_written`] this does not count overwrites.
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::{BufWriter, Write};
+    /// let mut wm = WriteMonitor::new(BufWriter::new(Vec::new()));
+    /// let monitor = wm.monitor();
+    /// wm.write_all(b"hello").unwrap();
+    /// assert_eq!(monitor.bytes_flushed(), 0);
+    /// wm.flush().unwrap();
+    /// assert_eq!(monitor.bytes_flushed(), 5);
+    /// ```
+    pub fn bytes_flushed(&self) -> u64 {
+        self.state.bytes_flushed()
+    }
+
+    /// Length of the start of the stream known to be on disk, see [`FileMonitor`](crate::FileMonitor).
+    ///
+    /// A transfer that crashed can safely resume from here.
+    /// Bytes synced past a hole left by seeking forward only count once the hole is written and synced too.
+    pub fn bytes_synced(&self) -> u64 {
+        self.state.bytes_synced()
+    }
+
+    /// Distinct bytes of the stream that were written, overwrites after seeking back are not counted.
+    ///
+    /// Bytes skipped by seeking forward only count once the writer goes back and writes them.
+    ///
+    /// This is what [`Monitor::fraction`], [`Monitor::remaining`] and [`Monitor::is_complete`] measure progress by.
+    /// For writers that never seek it is the same as [`Monitor::bytes_written`].
+    /// ```
+    /// use write_monitor::WriteMonitor;
+    /// use std::io::{Cursor, Seek, SeekFrom, Write};
+    /// let mut wm = WriteMonitor::new(Cursor::new(Vec::new()));
+    /// let monitor = wm.monitor();
+    /// wm.write_all(&[0; 100]).unwrap();
+    /// wm.seek(SeekFrom::Start(0)).unwrap();
+    /// wm.write_all(b"header").unwrap();
+    /// assert_eq!(monitor.bytes_written(), 106);
+    /// assert_eq!(monitor.unique_bytes_written(), 100);
+    /// assert_eq!(monitor.position(), 6);
+    /// assert_eq!(monitor.high_water_mark(), 100);
+    /// ```
+    pub fn unique_bytes_written(&self) -> u64 {
+        self.state.unique_bytes_written()
+    }
+
+    /// The current position of the writer in the stream, relative to where it started unless it seeked.
+    pub fn position(&self) -> u64 {
+        self.state.position()
+    }
+
+    /// The furthest position the writer has written up to, seeking past it does not move it.
+    pub fn high_water_mark(&self) -> u64 {
+        self.state.high_water_mark()
     }
 
+    /// The number of bytes expected to be written, if known.
+    pub fn total(&self) -> Option<u64> {
+        self.state.total()
+    }
+
+    /// Set or clear the number of bytes expected to be written.
+    ///
+    /// This is useful when the total is only known after the write started, for example from a late `Content-Length`.
+    pub fn set_total(&self, total: Option<u64>) {
+        self.state.set_total(total)
+    }
+
+    /// Fraction of the total that has been written, between `0.0` and `1.0`.
+    pub fn fraction(&self) -> Option<f64> {
+        let total = self.total()?;
+        if total == 0 {
+            return Some(1.0);
+        }
+        Some((self.unique_bytes_written() as f64 / total as f64).min(1.0))
+    }
+
+    /// Bytes left until the total is reached.
+    pub fn remaining(&self) -> Option<u64> {
+        Some(self.total()?.saturating_sub(self.unique_bytes_written()))
+    }
+
+    /// Whether the total is known and has been reached.
+    pub fn is_complete(&self) -> bool {
+        self.state.is_complete()
+    }
+
+    /// Hold back further writes until [`Monitor::resume`] is called.
+    ///
+    /// A paused [`WriteMonitor`] blocks the thread in `write` (std),
+    /// or returns [`Poll::Pending`](core::task::Poll::Pending) from `poll_write` (tokio, futures) and is woken on resume.
+    /// A write that is already in progress in the inner writer is not interrupted.
+    pub fn pause(&self) {
+        self.state.set_paused(true)
+    }
+
+    /// Let writes through again after [`Monitor::pause`].
+    pub fn resume(&self) {
+        self.state.set_paused(false)
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.is_paused()
+    }
+
+    /// Whether the writer has been shut down, closed or dropped, no more bytes will be written.
+    pub fn is_finished(&self) -> bool {
+        self.state.is_finished()
+    }
+
+    /// Move all counters to `offset`, as if exactly `offset` bytes had been written so far.
+    ///
+    /// Use this to roll back to the last acknowledged offset when retrying an upload.
+    /// Flushed and synced bytes past `offset` no longer count, moving forward does not flush or sync anything.
+    /// The rates do not count the move as throughput.
+    pub fn set(&self, offset: u64) {
+        self.state.rebase(offset)
+    }
+
+    /// Move all counters back to zero, see [`Monitor::set`].
+    pub fn reset(&self) {
+        self.set(0)
+    }
+
+    /// Move all counters back to `offset` if more than that has been written, returning whether they moved.
+    ///
+    /// See [`Monitor::set`].
+    /// ```
+    /// use write_monitor::{ManualClock, WriteMonitor};
+    /// use std::io::Write;
+    /// use std::time::Duration;
+    /// let clock = ManualClock::new();
+    /// let mut wm = WriteMonitor::with_clock(Vec::new(), clock.clone());
+    /// let monitor = wm.monitor();
+    /// wm.write_all(&[0; 1000]).unwrap();
+    /// clock.advance(Duration::from_secs(1));
+    /// assert_eq!(monitor.rate(), 1000.0);
+    /// assert!(monitor.rewind_to(400));
+    /// wm.write_all(&[0; 500]).unwrap();
+    /// clock.advance(Duration::from_secs(1));
+    /// assert_eq!(monitor.bytes_written(), 900);
+    /// assert_eq!(monitor.rate(), 500.0);
+    /// ```
+    pub fn rewind_to(&self, offset: u64) -> bool {
+        let rewind = offset < self.unique_bytes_written();
+        if rewind {
+            self.set(offset);
+        }
+        rewind
+    }
+
+    #[deprecated(
+        note = "use `Monitor::bytes_written` to read and `Monitor::set` to move the counter"
+    )]
     pub fn into_inner(self) -> Arc<AtomicU64> {
-        self.bytes_written
+        self.state.bytes_written.clone()
+    }
+}
+
+#[cfg(feature = "std")]
+impl Monitor {
+    /// Time since the first byte was written, zero if nothing has been written yet.
+    pub fn elapsed(&self) -> std::time::Duration {
+        self.state.meter.elapsed()
+    }
+
+    /// Bytes per second measured since the previous sample.
+    ///
+    /// Every call samples the counter, calls closer together than 100ms return the previous value.
+    pub fn rate(&self) -> f64 {
+        self.state.meter.sample(self.bytes_written()).0
+    }
+
+    /// Bytes per second smoothed with an exponentially weighted moving average over roughly 5 seconds.
+    pub fn smoothed_rate(&self) -> f64 {
+        self.state.meter.sample(self.bytes_written()).1
+    }
+
+    /// Estimated time left until the total has been written at the smoothed rate.
+    ///
+    /// Returns `None` while the total or the rate is still unknown.
+    pub fn eta(&self) -> Option<std::time::Duration> {
+        self.eta_for(self.total()?)
+    }
+
+    /// Estimated time left until `total` bytes have been written at the smoothed rate.
+    ///
+    /// Returns `None` while the rate is still unknown.
+    pub fn eta_for(&self, total: u64) -> Option<std::time::Duration> {
+        let remaining = total.saturating_sub(self.unique_bytes_written());
+        rate::eta(remaining, self.smoothed_rate())
     }
 }
 
@@ -81,19 +550,52 @@ impl<W: tokio::io::AsyncWrite + core::marker::Unpin> tokio::io::AsyncWrite for W
         buf: &[u8],
     ) -> core::task::Poll<std::io::Result<usize>> {
         let ah = self.project();
-        let r = ah.inner.poll_write(cx, buf);
-        if let Poll::Ready(Ok(n)) = r {
-            ah.bytes_written.fetch_add(n as u64, Ordering::AcqRel);
+        let timer = ah.timer.as_deref().unwrap_or(&TokioTimer);
+        let len = core::task::ready!(ah.state.poll_admit(cx, ah.delay, timer, buf.len()))?;
+        let r = ah.inner.poll_write(cx, &buf[..len]);
+        match &r {
+            Poll::Ready(r) => ah.state.record_write(r, len, buf.len()),
+            Poll::Pending => ah.state.release(len),
+        }
+        r
+    }
+
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        bufs: &[std::io::IoSlice<'_>],
+    ) -> core::task::Poll<std::io::Result<usize>> {
+        let ah = self.project();
+        let timer = ah.timer.as_deref().unwrap_or(&TokioTimer);
+        let requested = bufs.iter().map(|b| b.len()).sum();
+        let len = core::task::ready!(ah.state.poll_admit(cx, ah.delay, timer, requested))?;
+        let r = if len < requested {
+            ah.inner.poll_write_vectored(cx, &gate::truncate(bufs, len))
+        } else {
+            ah.inner.poll_write_vectored(cx, bufs)
+        };
+        match &r {
+            Poll::Ready(r) => ah.state.record_write(r, len, requested),
+            Poll::Pending => ah.state.release(len),
         }
         r
     }
 
+    fn is_write_vectored(&self) -> bool {
+        self.inner.is_write_vectored()
+    }
+
     fn poll_flush(
         self: Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<std::io::Result<()>> {
         let ah = self.project();
-        ah.inner.poll_flush(cx)
+        let bytes = ah.state.unique_bytes_written();
+        let r = ah.inner.poll_flush(cx);
+        if let Poll::Ready(r) = &r {
+            ah.state.record_flush(r, bytes);
+        }
+        r
     }
 
     fn poll_shutdown(
@@ -101,7 +603,12 @@ impl<W: tokio::io::AsyncWrite + core::marker::Unpin> tokio::io::AsyncWrite for W
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<std::io::Result<()>> {
         let ah = self.project();
-        ah.inner.poll_shutdown(cx)
+        let bytes = ah.state.unique_bytes_written();
+        let r = ah.inner.poll_shutdown(cx);
+        if let Poll::Ready(r) = &r {
+            ah.state.record_shutdown(r, bytes);
+        }
+        r
     }
 }
 
@@ -113,9 +620,32 @@ impl<W: futures::io::AsyncWrite + core::marker::Unpin> futures::io::AsyncWrite f
         buf: &[u8],
     ) -> core::task::Poll<futures::io::Result<usize>> {
         let ah = self.project();
-        let r = ah.inner.poll_write(cx, buf);
-        if let Poll::Ready(Ok(n)) = r {
-            ah.bytes_written.fetch_add(n as u64, Ordering::AcqRel);
+        let timer = ah.timer.as_deref().unwrap_or(&ThreadTimer);
+        let len = core::task::ready!(ah.state.poll_admit(cx, ah.delay, timer, buf.len()))?;
+        let r = ah.inner.poll_write(cx, &buf[..len]);
+        match &r {
+            Poll::Ready(r) => ah.state.record_write(r, len, buf.len()),
+            Poll::Pending => ah.state.release(len),
+        }
+        r
+    }
+    fn poll_write_vectored(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        bufs: &[futures::io::IoSlice<'_>],
+    ) -> core::task::Poll<futures::io::Result<usize>> {
+        let ah = self.project();
+        let timer = ah.timer.as_deref().unwrap_or(&ThreadTimer);
+        let requested = bufs.iter().map(|b| b.len()).sum();
+        let len = core::task::ready!(ah.state.poll_admit(cx, ah.delay, timer, requested))?;
+        let r = if len < requested {
+            ah.inner.poll_write_vectored(cx, &gate::truncate(bufs, len))
+        } else {
+            ah.inner.poll_write_vectored(cx, bufs)
+        };
+        match &r {
+            Poll::Ready(r) => ah.state.record_write(r, len, requested),
+            Poll::Pending => ah.state.release(len),
         }
         r
     }
@@ -124,27 +654,100 @@ impl<W: futures::io::AsyncWrite + core::marker::Unpin> futures::io::AsyncWrite f
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<futures::io::Result<()>> {
         let ah = self.project();
-        ah.inner.poll_flush(cx)
+        let bytes = ah.state.unique_bytes_written();
+        let r = ah.inner.poll_flush(cx);
+        if let Poll::Ready(r) = &r {
+            ah.state.record_flush(r, bytes);
+        }
+        r
     }
     fn poll_close(
         self: Pin<&mut Self>,
         cx: &mut core::task::Context<'_>,
     ) -> core::task::Poll<futures::io::Result<()>> {
         let ah = self.project();
-        ah.inner.poll_close(cx)
+        let bytes = ah.state.unique_bytes_written();
+        let r = ah.inner.poll_close(cx);
+        if let Poll::Ready(r) = &r {
+            ah.state.record_shutdown(r, bytes);
+        }
+        r
     }
 }
 
 #[cfg(feature = "std")]
 impl<W: std::io::Write> std::io::Write for WriteMonitor<W> {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
-        let r = std::io::Write::write(&mut self.inner, buf);
-        if let Ok(n) = r {
-            self.bytes_written.fetch_add(n as u64, Ordering::AcqRel);
-        }
+        let len = self.state.admit_blocking(buf.len())?;
+        let r = std::io::Write::write(&mut self.inner, &buf[..len]);
+        self.state.record_write(&r, len, buf.len());
+        r
+    }
+    fn write_vectored(&mut self, bufs: &[std::io::IoSlice<'_>]) -> std::io::Result<usize> {
+        let requested = bufs.iter().map(|b| b.len()).sum();
+        let len = self.state.admit_blocking(requested)?;
+        let r = if len < requested {
+            self.inner.write_vectored(&gate::truncate(bufs, len))
+        } else {
+            self.inner.write_vectored(bufs)
+        };
+        self.state.record_write(&r, len, requested);
         r
     }
     fn flush(&mut self) -> std::io::Result<()> {
-        self.inner.flush()
+        let bytes = self.state.unique_bytes_written();
+        let r = self.inner.flush();
+        self.state.record_flush(&r, bytes);
+        r
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+#[cfg(feature = "tokio")]
+impl<W: tokio::io::AsyncSeek + core::marker::Unpin> tokio::io::AsyncSeek for WriteMonitor<W> {
+    fn start_seek(self: Pin<&mut Self>, position: std::io::SeekFrom) -> std::io::Result<()> {
+        let ah = self.project();
+        let r = ah.inner.start_seek(position);
+        if let Err(e) = &r {
+            ah.state.failed(e.kind());
+        }
+        r
+    }
+
+    fn poll_complete(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<u64>> {
+        let ah = self.project();
+        let r = ah.inner.poll_complete(cx);
+        if let Poll::Ready(r) = &r {
+            ah.state.record_seek_result(r);
+        }
+        r
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<W: futures::io::AsyncSeek + core::marker::Unpin> futures::io::AsyncSeek for WriteMonitor<W> {
+    fn poll_seek(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        pos: futures::io::SeekFrom,
+    ) -> core::task::Poll<futures::io::Result<u64>> {
+        let ah = self.project();
+        let r = ah.inner.poll_seek(cx, pos);
+        if let Poll::Ready(r) = &r {
+            ah.state.record_seek_result(r);
+        }
+        r
+    }
+}
+
+#[cfg(feature = "std")]
+impl<W: std::io::Seek> std::io::Seek for WriteMonitor<W> {
+    fn seek(&mut self, pos: std::io::SeekFrom) -> std::io::Result<u64> {
+        let r = self.inner.seek(pos);
+        self.state.record_seek_result(&r);
+        r
     }
 }
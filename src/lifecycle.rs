@@ -0,0 +1,233 @@
+//! Tracking whether a monitored writer is still going, finished cleanly or was abandoned.
+use crate::{state::State, Monitor};
+use alloc::string::String;
+use core::sync::atomic::{AtomicU8, Ordering};
+use std::io::{self, ErrorKind};
+use std::sync::Mutex;
+
+/// Where a monitored writer (or reader) is in its life.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lifecycle {
+    /// Bytes are being written, or nothing happened yet.
+    Active,
+    /// Everything written so far was flushed successfully.
+    Flushed,
+    /// The writer was shut down (tokio) or closed (futures) successfully,
+    /// or dropped right after a successful flush.
+    /// A [`ReadMonitor`](crate::ReadMonitor) is closed when it reaches the end of the stream.
+    Closed,
+    /// The last operation failed with this error.
+    ///
+    /// [`ErrorKind::WouldBlock`] and [`ErrorKind::Interrupted`] are retried by callers and do not count.
+    Errored(ErrorKind),
+    /// The writer was dropped with unflushed bytes before reaching the total.
+    Dropped,
+    /// The transfer was cancelled through [`Monitor::cancel`], this is final.
+    Cancelled,
+}
+
+impl Lifecycle {
+    /// Whether the writer finished cleanly.
+    pub fn is_closed(&self) -> bool {
+        matches!(self, Self::Closed)
+    }
+
+    /// Whether the writer failed, was dropped halfway or was cancelled.
+    pub fn is_abandoned(&self) -> bool {
+        matches!(self, Self::Errored(_) | Self::Dropped | Self::Cancelled)
+    }
+
+    /// How the transfer of `monitor` ended, as shown by progress displays.
+    pub(crate) fn outcome(&self, monitor: &Monitor) -> String {
+        match self {
+            Self::Errored(kind) => alloc::format!("failed: {kind}"),
+            Self::Cancelled => match monitor.cancel_reason() {
+                Some(reason) => alloc::format!("cancelled: {reason}"),
+                None => "cancelled".into(),
+            },
+            Self::Dropped => "interrupted".into(),
+            Self::Active | Self::Flushed | Self::Closed => "done".into(),
+        }
+    }
+}
+
+const ACTIVE: u8 = 0;
+const FLUSHED: u8 = 1;
+const CLOSED: u8 = 2;
+const ERRORED: u8 = 3;
+const DROPPED: u8 = 4;
+const CANCELLED: u8 = 5;
+
+/// The [`Lifecycle`] of a [`State`], a single atomic on the hot path.
+#[derive(Debug, Default)]
+pub(crate) struct Tracker {
+    tag: AtomicU8,
+    error: Mutex<Option<ErrorKind>>,
+}
+
+impl Tracker {
+    pub(crate) fn get(&self) -> Lifecycle {
+        match self.tag.load(Ordering::Acquire) {
+            ACTIVE => Lifecycle::Active,
+            FLUSHED => Lifecycle::Flushed,
+            CLOSED => Lifecycle::Closed,
+            DROPPED => Lifecycle::Dropped,
+            CANCELLED => Lifecycle::Cancelled,
+            _ => Lifecycle::Errored(
+                self.error
+                    .lock()
+                    .unwrap_or_else(|e| e.into_inner())
+                    .unwrap_or(ErrorKind::Other),
+            ),
+        }
+    }
+
+    pub(crate) fn active(&self) {
+        if self.tag.load(Ordering::Relaxed) != ACTIVE {
+            self.set(ACTIVE);
+        }
+    }
+
+    /// Move to `tag`, unless the transfer was cancelled.
+    fn set(&self, tag: u8) {
+        let _ = self
+            .tag
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (current != CANCELLED).then_some(tag)
+            });
+    }
+
+    /// Move to cancelled, unless the writer already ended cleanly or was dropped.
+    pub(crate) fn cancelled(&self) {
+        let _ = self
+            .tag
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |current| {
+                (!matches!(current, CLOSED | DROPPED)).then_some(CANCELLED)
+            });
+    }
+
+    fn errored(&self, kind: ErrorKind) {
+        *self.error.lock().unwrap_or_else(|e| e.into_inner()) = Some(kind);
+        self.set(ERRORED);
+    }
+
+    /// The last writer went away, which is only clean right after a flush or once the total was reached.
+    pub(crate) fn dropped(&self, complete: bool) {
+        match self.tag.load(Ordering::Acquire) {
+            FLUSHED => self.set(CLOSED),
+            ACTIVE if complete => self.set(CLOSED),
+            ACTIVE => self.set(DROPPED),
+            _ => {}
+        }
+    }
+}
+
+impl State {
+    /// Account for the outcome of a write of `granted` out of the caller's `requested` bytes,
+    /// `granted` as returned by [`State::admit`].
+    pub(crate) fn record_write(&self, r: &io::Result<usize>, granted: usize, requested: usize) {
+        #[cfg(feature = "stats")]
+        self.stats.write(requested, r.as_ref().ok().copied());
+        #[cfg(not(feature = "stats"))]
+        let _ = requested;
+        match r {
+            Ok(n) => {
+                self.release(granted.saturating_sub(*n));
+                self.record(*n as u64);
+                self.quota.unreserve(*n as u64);
+            }
+            Err(e) => {
+                self.release(granted);
+                self.failed(e.kind())
+            }
+        }
+    }
+
+    /// Account for the outcome of a read of up to `requested` bytes, reading nothing means the end of the stream.
+    pub(crate) fn record_read(&self, r: Result<usize, ErrorKind>, requested: usize) {
+        match r {
+            Ok(0) if requested > 0 => self.record_close(&Ok(())),
+            Ok(n) => self.record(n as u64),
+            Err(kind) => self.failed(kind),
+        }
+    }
+
+    /// Account for the outcome of filling a read buffer, an empty buffer means the end of the stream.
+    pub(crate) fn record_fill(&self, r: Result<&[u8], ErrorKind>) {
+        match r {
+            Ok([]) => self.record_close(&Ok(())),
+            Ok(_) => {}
+            Err(kind) => self.failed(kind),
+        }
+    }
+
+    pub(crate) fn record_seek_result(&self, r: &io::Result<u64>) {
+        match r {
+            Ok(position) => self.record_seek(*position),
+            Err(e) => self.failed(e.kind()),
+        }
+    }
+
+    /// Account for a flush that started once `bytes` distinct bytes were written.
+    pub(crate) fn record_flush(&self, r: &io::Result<()>, bytes: u64) {
+        #[cfg(feature = "stats")]
+        self.stats.flush();
+        match r {
+            Ok(()) => {
+                self.flushed_up_to(bytes);
+                self.lifecycle.set(FLUSHED);
+                self.notify();
+            }
+            Err(e) => self.failed(e.kind()),
+        }
+    }
+
+    /// Account for a shutdown or close of a writer that started once `bytes` distinct bytes were written,
+    /// which flushes them too.
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    pub(crate) fn record_shutdown(&self, r: &io::Result<()>, bytes: u64) {
+        if r.is_ok() {
+            self.flushed_up_to(bytes);
+        }
+        self.record_close(r)
+    }
+
+    pub(crate) fn record_close(&self, r: &io::Result<()>) {
+        match r {
+            Ok(()) => {
+                self.lifecycle.set(CLOSED);
+                self.finish();
+                self.run_complete_hooks();
+            }
+            Err(e) => self.failed(e.kind()),
+        }
+    }
+
+    pub(crate) fn failed(&self, kind: ErrorKind) {
+        if !self.errors.record(kind) {
+            return;
+        }
+        self.lifecycle.errored(kind);
+        self.notify();
+        self.run_error_hooks(kind);
+    }
+}
+
+impl Monitor {
+    /// Where the monitored writer is in its [`Lifecycle`].
+    ///
+    /// Use this to tell a transfer that is done apart from one that was abandoned.
+    /// ```
+    /// use write_monitor::{Lifecycle, WriteMonitor};
+    /// use std::io::Write;
+    /// let mut wm = WriteMonitor::new(Vec::new());
+    /// let monitor = wm.monitor();
+    /// wm.write_all(b"hello").unwrap();
+    /// assert_eq!(monitor.lifecycle(), Lifecycle::Active);
+    /// drop(wm);
+    /// assert_eq!(monitor.lifecycle(), Lifecycle::Dropped);
+    /// ```
+    pub fn lifecycle(&self) -> Lifecycle {
+        self.state.lifecycle.get()
+    }
+}
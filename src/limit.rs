@@ -0,0 +1,253 @@
+//! Bandwidth limiting with a token bucket.
+use crate::Monitor;
+use core::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Duration;
+
+/// A bandwidth limit for a [`WriteMonitor`](crate::WriteMonitor).
+///
+/// Bytes are let through at `bytes_per_second` on average,
+/// with up to `burst` bytes at once after the writer was idle.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct RateLimit {
+    pub bytes_per_second: u64,
+    pub burst: u64,
+}
+
+impl RateLimit {
+    /// A limit of `bytes_per_second` with a burst of one second worth of bytes.
+    pub fn new(bytes_per_second: u64) -> Self {
+        Self {
+            bytes_per_second,
+            burst: bytes_per_second,
+        }
+    }
+
+    pub fn with_burst(self, burst: u64) -> Self {
+        Self { burst, ..self }
+    }
+}
+
+#[derive(Debug)]
+struct TokenBucket {
+    limit: RateLimit,
+    tokens: f64,
+    /// When `tokens` was last refilled.
+    at: Duration,
+}
+
+impl TokenBucket {
+    fn burst(&self) -> f64 {
+        self.limit.burst.max(1) as f64
+    }
+
+    fn refill(&mut self, now: Duration) {
+        let elapsed = now.saturating_sub(self.at).as_secs_f64();
+        self.tokens =
+            (self.tokens + elapsed * self.limit.bytes_per_second as f64).min(self.burst());
+        self.at = now;
+    }
+
+    /// Take up to `want` tokens, or return how long to wait until enough are available.
+    ///
+    /// Waits until either all of `want` or a full burst fits, so a slow limit does not turn into many tiny writes.
+    fn take(&mut self, want: u64, now: Duration) -> Result<u64, Duration> {
+        self.refill(now);
+        let need = (want as f64).min(self.burst());
+        if self.tokens >= need {
+            let granted = want.min(self.tokens as u64);
+            self.tokens -= granted as f64;
+            return Ok(granted);
+        }
+        match self.limit.bytes_per_second {
+            // Only a change of the limit can let anything through.
+            0 => Err(Duration::MAX),
+            rate => Err(Duration::from_secs_f64((need - self.tokens) / rate as f64)),
+        }
+    }
+}
+
+/// The shared, runtime adjustable [`RateLimit`] of a state.
+#[derive(Debug, Default)]
+pub(crate) struct Limiter {
+    enabled: AtomicBool,
+    bucket: Mutex<Option<TokenBucket>>,
+}
+
+impl Limiter {
+    fn lock(&self) -> MutexGuard<'_, Option<TokenBucket>> {
+        self.bucket.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn get(&self) -> Option<RateLimit> {
+        self.lock().as_ref().map(|bucket| bucket.limit)
+    }
+
+    /// Change the limit, keeping the tokens saved up so far.
+    pub(crate) fn set(&self, limit: Option<RateLimit>, now: Duration) {
+        let mut bucket = self.lock();
+        *bucket = limit.map(|limit| {
+            let mut bucket = bucket.take().unwrap_or(TokenBucket {
+                limit,
+                tokens: limit.burst as f64,
+                at: now,
+            });
+            bucket.refill(now);
+            bucket.limit = limit;
+            bucket.tokens = bucket.tokens.min(bucket.burst());
+            bucket
+        });
+        self.enabled.store(bucket.is_some(), Ordering::Release);
+    }
+
+    /// Whether a limit is set, checked before reading the clock for [`Limiter::take`].
+    pub(crate) fn is_enabled(&self) -> bool {
+        self.enabled.load(Ordering::Acquire)
+    }
+
+    /// Take up to `want` bytes worth of tokens, see [`TokenBucket::take`].
+    pub(crate) fn take(&self, want: u64, now: Duration) -> Result<u64, Duration> {
+        if !self.enabled.load(Ordering::Acquire) || want == 0 {
+            return Ok(want);
+        }
+        match self.lock().as_mut() {
+            Some(bucket) => bucket.take(want, now),
+            None => Ok(want),
+        }
+    }
+
+    /// Give back tokens for bytes that were granted but not written.
+    pub(crate) fn release(&self, n: u64) {
+        if n == 0 || !self.enabled.load(Ordering::Acquire) {
+            return;
+        }
+        if let Some(bucket) = self.lock().as_mut() {
+            bucket.tokens = (bucket.tokens + n as f64).min(bucket.burst());
+        }
+    }
+}
+
+impl Monitor {
+    /// The current bandwidth limit of the writer.
+    pub fn rate_limit(&self) -> Option<RateLimit> {
+        self.state.limiter.get()
+    }
+
+    /// Change or remove the bandwidth limit of the writer, this takes effect on the next write.
+    pub fn set_rate_limit(&self, limit: Option<RateLimit>) {
+        self.state.set_rate_limit(limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Limiter, RateLimit, TokenBucket};
+    use std::time::Duration;
+
+    fn bucket(limit: RateLimit) -> TokenBucket {
+        TokenBucket {
+            limit,
+            tokens: limit.burst as f64,
+            at: Duration::ZERO,
+        }
+    }
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn bucket_refills_at_the_rate_up_to_the_burst() {
+        let mut bucket = bucket(RateLimit::new(100));
+        assert_eq!(bucket.take(150, ms(0)), Ok(100));
+        assert_eq!(bucket.take(50, ms(0)), Err(ms(500)));
+        assert_eq!(bucket.take(50, ms(250)), Err(ms(250)));
+        assert_eq!(bucket.take(50, ms(500)), Ok(50));
+        assert_eq!(bucket.take(1000, ms(10_000)), Ok(100));
+    }
+
+    #[test]
+    fn bucket_waits_for_a_full_burst_at_most() {
+        let mut bucket = bucket(RateLimit::new(100).with_burst(10));
+        assert_eq!(bucket.take(1000, ms(0)), Ok(10));
+        assert_eq!(bucket.take(1000, ms(50)), Err(ms(50)));
+        assert_eq!(bucket.take(1000, ms(100)), Ok(10));
+    }
+
+    #[test]
+    fn zero_rate_waits_for_a_change() {
+        let mut bucket = bucket(RateLimit::new(0).with_burst(1));
+        assert_eq!(bucket.take(1, ms(0)), Ok(1));
+        assert_eq!(bucket.take(1, ms(1000)), Err(Duration::MAX));
+    }
+
+    #[test]
+    fn limiter_changes_keep_saved_tokens_within_the_new_burst() {
+        let limiter = Limiter::default();
+        assert_eq!(limiter.take(1000, ms(0)), Ok(1000));
+        limiter.set(Some(RateLimit::new(100)), ms(0));
+        assert_eq!(limiter.take(100, ms(0)), Ok(100));
+        limiter.set(Some(RateLimit::new(1000)), ms(0));
+        assert_eq!(limiter.take(500, ms(0)), Err(ms(500)));
+        assert_eq!(limiter.take(500, ms(500)), Ok(500));
+        limiter.set(Some(RateLimit::new(1000).with_burst(10)), ms(10_000));
+        assert_eq!(limiter.take(500, ms(10_000)), Ok(10));
+        limiter.release(5);
+        assert_eq!(limiter.take(5, ms(10_000)), Ok(5));
+        limiter.set(None, ms(10_000));
+        assert_eq!(limiter.take(500, ms(10_000)), Ok(500));
+    }
+
+    #[cfg(any(feature = "futures", feature = "tokio"))]
+    mod wait {
+        use crate::{Clock, ManualClock, RateLimit, Sleep, Timer, WriteMonitor};
+        use std::time::Duration;
+
+        /// A timer that moves the clock forward instead of sleeping.
+        struct Advance(ManualClock);
+
+        impl Timer for Advance {
+            fn sleep(&self, duration: Duration) -> Sleep {
+                self.0.advance(duration);
+                Box::pin(core::future::ready(()))
+            }
+        }
+
+        fn limited(limit: RateLimit) -> (WriteMonitor<Vec<u8>>, ManualClock) {
+            let clock = ManualClock::new();
+            let mut wm = WriteMonitor::with_clock(Vec::new(), clock.clone());
+            wm.set_timer(Advance(clock.clone()));
+            wm.monitor().set_rate_limit(Some(limit));
+            (wm, clock)
+        }
+
+        #[cfg(feature = "tokio")]
+        #[test]
+        fn tokio_write_waits_for_the_limit() {
+            use tokio::io::AsyncWriteExt;
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .build()
+                .unwrap();
+            let (mut wm, clock) = limited(RateLimit::new(100));
+            rt.block_on(wm.write_all(&[0; 250])).unwrap();
+            assert_eq!(clock.now(), Duration::from_millis(1500));
+            wm.monitor().set_rate_limit(Some(RateLimit::new(1000)));
+            rt.block_on(wm.write_all(&[0; 500])).unwrap();
+            assert_eq!(clock.now(), Duration::from_millis(2000));
+            assert_eq!(wm.get_ref().len(), 750);
+        }
+
+        #[cfg(feature = "futures")]
+        #[test]
+        fn futures_write_waits_for_the_limit() {
+            use futures::io::AsyncWriteExt;
+            let (mut wm, clock) = limited(RateLimit::new(100));
+            futures::executor::block_on(wm.write_all(&[0; 250])).unwrap();
+            assert_eq!(clock.now(), Duration::from_millis(1500));
+            wm.monitor().set_rate_limit(Some(RateLimit::new(1000)));
+            futures::executor::block_on(wm.write_all(&[0; 500])).unwrap();
+            assert_eq!(clock.now(), Duration::from_millis(2000));
+            assert_eq!(wm.get_ref().len(), 750);
+        }
+    }
+}
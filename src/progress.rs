@@ -0,0 +1,91 @@
+use crate::{state::State, Bytes, BytesPerSecond, Monitor, Units};
+use std::time::Duration;
+
+/// A snapshot of a [`Monitor`] at one point in time.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Progress {
+    /// Bytes written so far.
+    pub bytes: u64,
+    /// Distinct bytes written so far, see [`Monitor::unique_bytes_written`].
+    pub unique: u64,
+    /// The expected total, if known.
+    pub total: Option<u64>,
+    /// Smoothed rate in bytes per second.
+    pub rate: f64,
+    /// Time since the first byte was written.
+    pub elapsed: Duration,
+}
+
+/// Shows the bytes written, the total and the rate, such as `1.5 MiB / 3.0 MiB (50%) at 1.2 MiB/s`.
+///
+/// Like [`Monitor::fraction`] the percentage does not count overwrites.
+/// The alternate flag `{:#}` shows [`Units::Si`] instead of [`Units::Iec`].
+/// ```
+/// use write_monitor::Progress;
+/// use std::time::Duration;
+/// let progress = Progress {
+///     bytes: 1536 * 1024,
+///     unique: 1536 * 1024,
+///     total: Some(3 * 1024 * 1024),
+///     rate: 1258291.2,
+///     elapsed: Duration::from_secs(1),
+/// };
+/// assert_eq!(progress.to_string(), "1.5 MiB / 3.0 MiB (50%) at 1.2 MiB/s");
+/// ```
+impl core::fmt::Display for Progress {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        let units = if f.alternate() { Units::Si } else { Units::Iec };
+        let bytes = |bytes| Bytes { bytes, units };
+        write!(f, "{}", bytes(self.bytes))?;
+        if let Some(total) = self.total {
+            let percent = match total {
+                0 => 100.0,
+                total => (self.unique as f64 / total as f64 * 100.0).min(100.0),
+            };
+            write!(f, " / {} ({percent:.0}%)", bytes(total))?;
+        }
+        let rate = BytesPerSecond {
+            bytes_per_second: self.rate,
+            units,
+        };
+        write!(f, " at {rate}")
+    }
+}
+
+impl State {
+    pub(crate) fn progress(&self) -> Progress {
+        let bytes = self.bytes_written();
+        Progress {
+            bytes,
+            unique: self.unique_bytes_written(),
+            total: self.total(),
+            rate: self.meter.sample(bytes).1,
+            elapsed: self.meter.elapsed(),
+        }
+    }
+}
+
+impl Monitor {
+    /// Take a [`Progress`] snapshot.
+    pub fn progress(&self) -> Progress {
+        self.state.progress()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ManualClock, WriteMonitor};
+    use std::io::{Cursor, Seek, SeekFrom, Write};
+
+    #[test]
+    fn overwrites_do_not_count_towards_the_percentage() {
+        let mut wm = WriteMonitor::with_clock(Cursor::new(Vec::new()), ManualClock::new());
+        wm.monitor().set_total(Some(100));
+        wm.write_all(&[0; 50]).unwrap();
+        wm.seek(SeekFrom::Start(0)).unwrap();
+        wm.write_all(&[0; 50]).unwrap();
+        let progress = wm.monitor().progress();
+        assert_eq!((progress.bytes, progress.unique), (100, 50));
+        assert_eq!(progress.to_string(), "100 B / 100 B (50%) at 0 B/s");
+    }
+}
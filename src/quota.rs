@@ -0,0 +1,162 @@
+//! Enforcing a maximum size on a [`WriteMonitor`].
+use crate::{state::State, Monitor, WriteMonitor};
+use core::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::io;
+
+/// What a [`WriteMonitor`] does with a write that does not fit in its limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+pub enum QuotaPolicy {
+    /// Write the bytes that still fit, the next write fails with [`QuotaExceeded`].
+    #[default]
+    Truncate,
+    /// Fail the whole write with [`QuotaExceeded`] without writing anything.
+    Reject,
+}
+
+/// The error returned by writes past the limit of a [`WriteMonitor`].
+///
+/// It is wrapped in an [`io::Error`] of kind [`io::ErrorKind::Other`], use [`QuotaExceeded::find`] to recognise it.
+/// The write never reaches the inner writer, so like one failing with [`Cancelled`](crate::Cancelled)
+/// it is not counted by [`Monitor::error_count`] and does not run [`WriteMonitor::on_error`] callbacks.
+/// ```
+/// use write_monitor::{Lifecycle, QuotaExceeded, QuotaPolicy, WriteMonitor};
+/// use std::io::Write;
+/// let mut wm = WriteMonitor::with_limit(Vec::new(), 4, QuotaPolicy::Truncate);
+/// let monitor = wm.monitor();
+/// assert_eq!(wm.write(b"hello").unwrap(), 4);
+/// assert_eq!(monitor.quota_remaining(), Some(0));
+/// let err = wm.write(b"o").unwrap_err();
+/// assert_eq!(QuotaExceeded::find(&err).map(QuotaExceeded::limit), Some(4));
+/// assert_eq!(monitor.lifecycle(), Lifecycle::Active);
+/// assert_eq!(monitor.error_count(), 0);
+///
+/// let mut wm = WriteMonitor::with_limit(Vec::new(), 4, QuotaPolicy::Reject);
+/// assert!(wm.write_all(b"hello").is_err());
+/// assert_eq!(wm.bytes_written(), 0);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct QuotaExceeded {
+    limit: u64,
+}
+
+impl QuotaExceeded {
+    pub fn limit(&self) -> u64 {
+        self.limit
+    }
+
+    /// The `QuotaExceeded` inside `error`, if it is one.
+    pub fn find(error: &io::Error) -> Option<&Self> {
+        error.get_ref()?.downcast_ref()
+    }
+}
+
+impl core::fmt::Display for QuotaExceeded {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        write!(f, "write exceeds the limit of {} bytes", self.limit)
+    }
+}
+
+impl std::error::Error for QuotaExceeded {}
+
+/// The limit of a [`State`], [`u64::MAX`] while there is none.
+#[derive(Debug)]
+pub(crate) struct Quota {
+    limit: AtomicU64,
+    reject: AtomicBool,
+    /// Bytes granted to writes that are still going on, they count against the limit until recorded.
+    reserved: AtomicU64,
+}
+
+impl Default for Quota {
+    fn default() -> Self {
+        Self {
+            limit: AtomicU64::new(u64::MAX),
+            reject: AtomicBool::new(false),
+            reserved: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Quota {
+    pub(crate) fn get(&self) -> Option<u64> {
+        match self.limit.load(Ordering::Acquire) {
+            u64::MAX => None,
+            limit => Some(limit),
+        }
+    }
+
+    pub(crate) fn policy(&self) -> QuotaPolicy {
+        match self.reject.load(Ordering::Acquire) {
+            true => QuotaPolicy::Reject,
+            false => QuotaPolicy::Truncate,
+        }
+    }
+
+    pub(crate) fn set(&self, limit: u64, policy: QuotaPolicy) {
+        self.reject
+            .store(policy == QuotaPolicy::Reject, Ordering::Release);
+        self.limit.store(limit.min(u64::MAX - 1), Ordering::Release);
+    }
+
+    /// Give back `n` reserved bytes, once they were recorded or will not be written.
+    pub(crate) fn unreserve(&self, n: u64) {
+        if n == 0 || self.reserved.load(Ordering::Acquire) == 0 {
+            return;
+        }
+        let _ = self
+            .reserved
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |r| {
+                Some(r.saturating_sub(n))
+            });
+    }
+}
+
+impl State {
+    /// Reserve as many of `want` bytes as fit in the limit at the current position,
+    /// next to those reserved by other writers sharing the state.
+    ///
+    /// The reservation is given back with [`Quota::unreserve`].
+    pub(crate) fn reserve_quota(&self, want: usize) -> io::Result<usize> {
+        let Some(limit) = self.quota.get() else {
+            return Ok(want);
+        };
+        let reject = self.quota.reject.load(Ordering::Acquire);
+        let mut fits = 0;
+        let reserved =
+            self.quota
+                .reserved
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |reserved| {
+                    let left = limit.saturating_sub(self.position().saturating_add(reserved));
+                    fits = usize::try_from(left).map_or(want, |left| want.min(left));
+                    (fits == want || (fits > 0 && !reject)).then(|| reserved + fits as u64)
+                });
+        match reserved {
+            Ok(_) => Ok(fits),
+            Err(_) => Err(io::Error::other(QuotaExceeded { limit })),
+        }
+    }
+}
+
+impl<W> WriteMonitor<W> {
+    /// Create a `WriteMonitor` that lets at most `limit` bytes through to `inner`.
+    ///
+    /// The limit bounds the size of the stream, bytes rewritten after seeking back are not counted twice.
+    pub fn with_limit(inner: W, limit: u64, policy: QuotaPolicy) -> Self {
+        let this = Self::new(inner);
+        this.state.quota.set(limit, policy);
+        this
+    }
+}
+
+impl Monitor {
+    /// The maximum size of the stream given to [`WriteMonitor::with_limit`].
+    pub fn limit(&self) -> Option<u64> {
+        self.state.quota.get()
+    }
+
+    /// How many more bytes can be written before reaching the limit, not counting writes that are going on.
+    pub fn quota_remaining(&self) -> Option<u64> {
+        let limit = self.state.quota.get()?;
+        Some(limit.saturating_sub(self.state.position()))
+    }
+}
@@ -0,0 +1,209 @@
+//! Throughput and ETA computation for [`Monitor`](crate::Monitor).
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// Samples closer together than this reuse the previously computed rates.
+const MIN_SAMPLE_INTERVAL: Duration = Duration::from_millis(100);
+/// Time constant of the exponentially weighted moving average.
+const SMOOTHING_WINDOW: Duration = Duration::from_secs(5);
+
+/// A monotonic source of time used for rate and ETA computations.
+///
+/// The returned [`Duration`] is measured from an arbitrary but fixed point in time.
+/// Implement this to drive a [`Monitor`](crate::Monitor) with a deterministic clock in tests,
+/// or use [`ManualClock`].
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+}
+
+impl<C: Clock + ?Sized> Clock for Arc<C> {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// The default [`Clock`] backed by [`std::time::Instant`].
+#[derive(Debug, Clone, Copy)]
+pub struct SystemClock {
+    base: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self {
+            base: Instant::now(),
+        }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.base.elapsed()
+    }
+}
+
+/// A [`Clock`] that only moves when told to.
+///
+/// Clones share the same time so one can be handed to a monitor while the other is advanced.
+/// ```
+/// use write_monitor::{ManualClock, WriteMonitor};
+/// use std::io::Write;
+/// use std::time::Duration;
+/// let clock = ManualClock::new();
+/// let mut wm = WriteMonitor::with_clock(Vec::new(), clock.clone());
+/// let monitor = wm.monitor();
+/// monitor.set_total(Some(2000));
+/// wm.write_all(&[0; 1000]).unwrap();
+/// clock.advance(Duration::from_secs(2));
+/// assert_eq!(monitor.elapsed(), Duration::from_secs(2));
+/// assert_eq!(monitor.rate(), 500.0);
+/// assert_eq!(monitor.eta(), Some(Duration::from_secs(2)));
+/// assert_eq!(monitor.fraction(), Some(0.5));
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ManualClock {
+    nanos: Arc<AtomicU64>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn advance(&self, by: Duration) {
+        self.nanos.fetch_add(by.as_nanos() as u64, Ordering::AcqRel);
+    }
+
+    pub fn set(&self, to: Duration) {
+        self.nanos.store(to.as_nanos() as u64, Ordering::Release);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        Duration::from_nanos(self.nanos.load(Ordering::Acquire))
+    }
+}
+
+/// Tracks when the first byte went through and the rates derived from sampling the counter.
+pub(crate) struct Meter {
+    clock: Arc<dyn Clock>,
+    /// Nanoseconds of the first write plus one, zero while nothing has been written.
+    started: AtomicU64,
+    samples: Mutex<Samples>,
+}
+
+#[derive(Debug, Default)]
+struct Samples {
+    at: Option<Duration>,
+    bytes: u64,
+    instant: f64,
+    smoothed: Option<f64>,
+}
+
+impl core::fmt::Debug for Meter {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Meter")
+            .field("started", &self.started_at())
+            .field("samples", &self.samples)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Meter {
+    pub(crate) fn new(clock: Arc<dyn Clock>) -> Self {
+        Self {
+            clock,
+            started: AtomicU64::new(0),
+            samples: Mutex::new(Samples::default()),
+        }
+    }
+
+    pub(crate) fn now(&self) -> Duration {
+        self.clock.now()
+    }
+
+    pub(crate) fn clock(&self) -> Arc<dyn Clock> {
+        self.clock.clone()
+    }
+
+    /// Record the time of the first write, later calls are a single atomic load.
+    pub(crate) fn start(&self) {
+        if self.started.load(Ordering::Relaxed) == 0 {
+            let now = self.now().as_nanos() as u64 + 1;
+            let _ = self
+                .started
+                .compare_exchange(0, now, Ordering::AcqRel, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn started_at(&self) -> Option<Duration> {
+        match self.started.load(Ordering::Acquire) {
+            0 => None,
+            n => Some(Duration::from_nanos(n - 1)),
+        }
+    }
+
+    pub(crate) fn elapsed(&self) -> Duration {
+        self.started_at()
+            .map(|started| self.now().saturating_sub(started))
+            .unwrap_or_default()
+    }
+
+    /// The counter was moved from `from` to `to` without writing, keep that out of the rates.
+    pub(crate) fn rebase(&self, from: u64, to: u64) {
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        samples.bytes = samples.bytes.saturating_add(to).saturating_sub(from);
+    }
+
+    /// Sample the counter and return the `(instantaneous, smoothed)` rates in bytes per second.
+    pub(crate) fn sample(&self, bytes: u64) -> (f64, f64) {
+        let Some(started) = self.started_at() else {
+            return (0.0, 0.0);
+        };
+        let now = self.now();
+        let mut samples = self.samples.lock().unwrap_or_else(|e| e.into_inner());
+        let (at, last_bytes) = match samples.at {
+            Some(at) => (at, samples.bytes),
+            None => (started, samples.bytes),
+        };
+        let dt = now.saturating_sub(at);
+        if dt < MIN_SAMPLE_INTERVAL {
+            return (samples.instant, samples.smoothed.unwrap_or(samples.instant));
+        }
+        let instant = bytes.saturating_sub(last_bytes) as f64 / dt.as_secs_f64();
+        let smoothed = match samples.smoothed {
+            Some(smoothed) => {
+                let alpha = 1.0 - (-dt.as_secs_f64() / SMOOTHING_WINDOW.as_secs_f64()).exp();
+                smoothed + alpha * (instant - smoothed)
+            }
+            None => instant,
+        };
+        *samples = Samples {
+            at: Some(now),
+            bytes,
+            instant,
+            smoothed: Some(smoothed),
+        };
+        (instant, smoothed)
+    }
+}
+
+/// Estimated time until `remaining` bytes are done at `rate` bytes per second.
+pub(crate) fn eta(remaining: u64, rate: f64) -> Option<Duration> {
+    if remaining == 0 {
+        return Some(Duration::ZERO);
+    }
+    if rate <= 0.0 || !rate.is_finite() {
+        return None;
+    }
+    Some(Duration::from_secs_f64(remaining as f64 / rate))
+}
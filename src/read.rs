@@ -0,0 +1,302 @@
+use crate::{
+    state::{Handle, State},
+    Monitor,
+};
+
+#[cfg(any(feature = "futures", feature = "tokio"))]
+use core::{pin::Pin, task::Poll};
+
+/// `ReadMonitor` wraps over a reader and monitors how many bytes are read from it.
+///
+/// It hands out the same [`Monitor`] as [`WriteMonitor`](crate::WriteMonitor) so progress can be
+/// observed the same way for uploads and downloads.
+/// For a `ReadMonitor` [`Monitor::bytes_written`] reports the number of bytes read.
+/// ```
+/// use write_monitor::{Lifecycle, ReadMonitor};
+/// use std::io::Read;
+/// let mut rm = ReadMonitor::with_total(&b"hello world"[..], 11);
+/// let monitor = rm.monitor();
+/// let mut buf = String::new();
+/// rm.read_to_string(&mut buf).unwrap();
+/// assert_eq!(monitor.bytes_written(), 11);
+/// assert!(monitor.is_complete());
+/// assert_eq!(monitor.lifecycle(), Lifecycle::Closed);
+/// ```
+#[cfg_attr(any(feature = "futures", feature = "tokio"), pin_project::pin_project)]
+#[derive(Debug)]
+pub struct ReadMonitor<R> {
+    #[cfg_attr(any(feature = "futures", feature = "tokio"), pin)]
+    inner: R,
+    state: Handle,
+}
+
+/// Cloning a `ReadMonitor` is the same as [`ReadMonitor::fork`],
+/// use [`ReadMonitor::clone_shared`] to keep counting into the same [`Monitor`].
+impl<R: Clone> Clone for ReadMonitor<R> {
+    fn clone(&self) -> Self {
+        self.fork()
+    }
+}
+
+impl<R: Clone> ReadMonitor<R> {
+    /// Clone the inner reader with a counter of its own, see [`WriteMonitor::fork`](crate::WriteMonitor::fork).
+    pub fn fork(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: Handle::new(self.state.fork()),
+        }
+    }
+
+    /// Clone the inner reader, counting into the same [`Monitor`].
+    pub fn clone_shared(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+        }
+    }
+}
+
+impl<R> ReadMonitor<R> {
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            state: Handle::new(State::new()),
+        }
+    }
+
+    /// Create a `ReadMonitor` that counts into the same state as `monitor`.
+    pub fn with_monitor(inner: R, monitor: &Monitor) -> Self {
+        Self {
+            inner,
+            state: Handle::attach(monitor.state.clone()),
+        }
+    }
+
+    /// Create a `ReadMonitor` for a transfer resumed at `offset`, which counts as already read.
+    pub fn with_offset(inner: R, offset: u64) -> Self {
+        let this = Self::new(inner);
+        this.state.resume_at(offset);
+        this
+    }
+
+    /// Create a `ReadMonitor` that expects `total` bytes to be read.
+    pub fn with_total(inner: R, total: u64) -> Self {
+        let this = Self::new(inner);
+        this.state.set_total(Some(total));
+        this
+    }
+
+    /// Create a `ReadMonitor` that measures rates and elapsed time with `clock`.
+    #[cfg(feature = "std")]
+    pub fn with_clock(inner: R, clock: impl crate::Clock + 'static) -> Self {
+        Self {
+            inner,
+            state: Handle::new(State::with_clock(alloc::sync::Arc::new(clock))),
+        }
+    }
+
+    pub fn bytes_read(&self) -> u64 {
+        self.state.bytes_written()
+    }
+
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Reading from the inner reader directly is not monitored.
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Take the inner reader back, this finishes the [`Monitor`] like dropping the `ReadMonitor` does.
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+
+    pub fn monitor(&self) -> Monitor {
+        Monitor::new(self.state.shared())
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncRead + core::marker::Unpin> tokio::io::AsyncRead for ReadMonitor<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> core::task::Poll<std::io::Result<()>> {
+        let ah = self.project();
+        let before = buf.filled().len();
+        let requested = buf.remaining();
+        let r = ah.inner.poll_read(cx, buf);
+        if let Poll::Ready(r) = &r {
+            let n = buf.filled().len() - before;
+            let r = r.as_ref().map(|()| n).map_err(std::io::Error::kind);
+            ah.state.record_read(r, requested);
+        }
+        r
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+#[cfg(feature = "tokio")]
+impl<R: tokio::io::AsyncBufRead + core::marker::Unpin> tokio::io::AsyncBufRead for ReadMonitor<R> {
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<std::io::Result<&[u8]>> {
+        let ah = self.project();
+        let r = ah.inner.poll_fill_buf(cx);
+        if let Poll::Ready(r) = &r {
+            let r = r.as_deref().map_err(std::io::Error::kind);
+            ah.state.record_fill(r);
+        }
+        r
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let ah = self.project();
+        ah.inner.consume(amt);
+        ah.state.record(amt as u64);
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<R: futures::io::AsyncRead + core::marker::Unpin> futures::io::AsyncRead for ReadMonitor<R> {
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+        buf: &mut [u8],
+    ) -> core::task::Poll<futures::io::Result<usize>> {
+        let ah = self.project();
+        let r = ah.inner.poll_read(cx, buf);
+        if let Poll::Ready(r) = &r {
+            let r = r.as_ref().copied().map_err(std::io::Error::kind);
+            ah.state.record_read(r, buf.len());
+        }
+        r
+    }
+}
+
+#[cfg(feature = "futures")]
+impl<R: futures::io::AsyncBufRead + core::marker::Unpin> futures::io::AsyncBufRead
+    for ReadMonitor<R>
+{
+    fn poll_fill_buf(
+        self: Pin<&mut Self>,
+        cx: &mut core::task::Context<'_>,
+    ) -> core::task::Poll<futures::io::Result<&[u8]>> {
+        let ah = self.project();
+        let r = ah.inner.poll_fill_buf(cx);
+        if let Poll::Ready(r) = &r {
+            let r = r.as_deref().map_err(std::io::Error::kind);
+            ah.state.record_fill(r);
+        }
+        r
+    }
+
+    fn consume(self: Pin<&mut Self>, amt: usize) {
+        let ah = self.project();
+        ah.inner.consume(amt);
+        ah.state.record(amt as u64);
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::Read> std::io::Read for ReadMonitor<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let r = self.inner.read(buf);
+        let res = r.as_ref().copied().map_err(std::io::Error::kind);
+        self.state.record_read(res, buf.len());
+        r
+    }
+}
+
+#[cfg(feature = "std")]
+impl<R: std::io::BufRead> std::io::BufRead for ReadMonitor<R> {
+    fn fill_buf(&mut self) -> std::io::Result<&[u8]> {
+        let r = self.inner.fill_buf();
+        let res = r.as_deref().map_err(std::io::Error::kind);
+        self.state.record_fill(res);
+        r
+    }
+
+    fn consume(&mut self, amt: usize) {
+        self.inner.consume(amt);
+        self.state.record(amt as u64);
+    }
+}
+
+#[cfg(all(test, feature = "std"))]
+mod tests {
+    use super::ReadMonitor;
+    use crate::Lifecycle;
+
+    const DATA: &[u8] = b"hello world";
+
+    #[test]
+    fn buf_read_counts_consumed_bytes() {
+        use std::io::BufRead;
+        let mut rm = ReadMonitor::new(DATA);
+        let monitor = rm.monitor();
+        assert_eq!(rm.fill_buf().unwrap(), DATA);
+        assert_eq!(monitor.bytes_written(), 0);
+        rm.consume(5);
+        assert_eq!(monitor.bytes_written(), 5);
+        assert_eq!(monitor.lifecycle(), Lifecycle::Active);
+        rm.consume(6);
+        assert!(rm.fill_buf().unwrap().is_empty());
+        assert_eq!(monitor.bytes_written(), 11);
+        assert_eq!(monitor.lifecycle(), Lifecycle::Closed);
+    }
+
+    #[test]
+    fn reading_into_an_empty_buffer_is_not_the_end() {
+        use std::io::Read;
+        let mut rm = ReadMonitor::new(DATA);
+        let monitor = rm.monitor();
+        assert_eq!(rm.read(&mut []).unwrap(), 0);
+        assert_eq!(monitor.lifecycle(), Lifecycle::Active);
+        assert!(!monitor.is_finished());
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn tokio_reads_are_counted_until_the_end() {
+        use tokio::io::{AsyncBufReadExt, AsyncReadExt};
+        let rt = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        rt.block_on(async {
+            let mut rm = ReadMonitor::new(DATA);
+            let monitor = rm.monitor();
+            let mut buf = [0; 5];
+            rm.read_exact(&mut buf).await.unwrap();
+            assert_eq!(monitor.bytes_written(), 5);
+            assert_eq!(rm.fill_buf().await.unwrap(), b" world");
+            rm.consume(6);
+            assert_eq!(monitor.bytes_written(), 11);
+            assert_eq!(rm.read(&mut buf).await.unwrap(), 0);
+            assert_eq!(monitor.lifecycle(), Lifecycle::Closed);
+        });
+    }
+
+    #[cfg(feature = "futures")]
+    #[test]
+    fn futures_reads_are_counted_until_the_end() {
+        use futures::io::{AsyncBufReadExt, AsyncReadExt};
+        futures::executor::block_on(async {
+            let mut rm = ReadMonitor::new(DATA);
+            let monitor = rm.monitor();
+            let mut buf = [0; 5];
+            rm.read_exact(&mut buf).await.unwrap();
+            assert_eq!(monitor.bytes_written(), 5);
+            assert_eq!(rm.fill_buf().await.unwrap(), b" world");
+            rm.consume_unpin(6);
+            assert_eq!(monitor.bytes_written(), 11);
+            assert_eq!(rm.read(&mut buf).await.unwrap(), 0);
+            assert_eq!(monitor.lifecycle(), Lifecycle::Closed);
+        });
+    }
+}
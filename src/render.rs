@@ -0,0 +1,236 @@
+//! Drawing a progress line for a [`Monitor`] without external dependencies.
+use crate::{Bytes, BytesPerSecond, HumanDuration, Monitor, Units};
+use alloc::boxed::Box;
+use alloc::string::String;
+use core::fmt::Write as _;
+use std::io::{self, IsTerminal, Write};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+
+/// Width of the bar between the brackets.
+const BAR_WIDTH: usize = 30;
+
+/// Longest the renderer sleeps before checking whether the writer finished.
+const FINISH_CHECK: Duration = Duration::from_millis(100);
+
+/// Draws the progress of a [`Monitor`] to an [`io::Write`], stderr by default.
+///
+/// On a terminal a single line with a bar, the bytes written, the rate and the ETA is redrawn in place.
+/// Otherwise a plain log line is written every refresh.
+/// Either way a last line tells how the transfer ended.
+/// ```
+/// use write_monitor::{Renderer, WriteMonitor};
+/// use std::io::Write;
+/// let mut wm = WriteMonitor::with_total(std::io::sink(), 1024);
+/// let renderer = Renderer::new(wm.monitor()).spawn();
+/// wm.write_all(&[0; 1024]).unwrap();
+/// drop(wm);
+/// renderer.join().unwrap().unwrap();
+/// ```
+pub struct Renderer {
+    monitor: Monitor,
+    output: Box<dyn Write + Send>,
+    terminal: bool,
+    refresh: Option<Duration>,
+    units: Units,
+}
+
+impl core::fmt::Debug for Renderer {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("Renderer")
+            .field("monitor", &self.monitor)
+            .field("terminal", &self.terminal)
+            .field("refresh", &self.refresh)
+            .field("units", &self.units)
+            .finish_non_exhaustive()
+    }
+}
+
+impl Renderer {
+    /// Draw to stderr, in place if it is a terminal.
+    pub fn new(monitor: Monitor) -> Self {
+        Self {
+            monitor,
+            output: Box::new(io::stderr()),
+            terminal: io::stderr().is_terminal(),
+            refresh: None,
+            units: Units::Iec,
+        }
+    }
+
+    /// Draw to `output` instead, which is assumed not to be a terminal unless told with [`Renderer::terminal`].
+    pub fn with_output(self, output: impl Write + Send + 'static) -> Self {
+        Self {
+            output: Box::new(output),
+            terminal: false,
+            ..self
+        }
+    }
+
+    /// Whether to redraw a single line in place rather than write log lines.
+    pub fn terminal(self, terminal: bool) -> Self {
+        Self { terminal, ..self }
+    }
+
+    /// How often to draw, by default every 100ms on a terminal and every 5s otherwise.
+    pub fn refresh(self, refresh: Duration) -> Self {
+        Self {
+            refresh: Some(refresh),
+            ..self
+        }
+    }
+
+    /// Show byte counts and rates in `units`, [`Units::Iec`] by default.
+    pub fn units(self, units: Units) -> Self {
+        Self { units, ..self }
+    }
+
+    /// Draw on a background thread until the writer finishes.
+    pub fn spawn(self) -> JoinHandle<io::Result<()>> {
+        std::thread::spawn(move || self.run())
+    }
+
+    /// Draw on this thread until the writer finishes.
+    pub fn run(mut self) -> io::Result<()> {
+        let refresh = self.refresh.unwrap_or(if self.terminal {
+            Duration::from_millis(100)
+        } else {
+            Duration::from_secs(5)
+        });
+        let mut line = String::new();
+        while !self.monitor.is_finished() {
+            line.clear();
+            self.describe(&mut line);
+            self.draw(&line)?;
+            // Sleep rather than wait for changes, which would make every write wake this thread.
+            let deadline = Instant::now() + refresh;
+            while let Some(left) = deadline.checked_duration_since(Instant::now()) {
+                if self.monitor.is_finished() || left.is_zero() {
+                    break;
+                }
+                std::thread::sleep(left.min(FINISH_CHECK));
+            }
+        }
+        line.clear();
+        self.describe(&mut line);
+        let _ = write!(
+            line,
+            "  {}",
+            self.monitor.lifecycle().outcome(&self.monitor)
+        );
+        self.draw(&line)?;
+        if self.terminal {
+            self.output.write_all(b"\n")?;
+        }
+        self.output.flush()
+    }
+
+    fn draw(&mut self, line: &str) -> io::Result<()> {
+        if self.terminal {
+            // Return to the start of the line and clear what is left of the previous one.
+            write!(self.output, "\r{line}\x1b[K")?;
+        } else {
+            writeln!(self.output, "{line}")?;
+        }
+        self.output.flush()
+    }
+
+    fn describe(&self, line: &mut String) {
+        let progress = self.monitor.progress();
+        let written = self.monitor.unique_bytes_written();
+        if let (Some(fraction), true) = (self.monitor.fraction(), self.terminal) {
+            let filled = (fraction * BAR_WIDTH as f64) as usize;
+            line.push('[');
+            line.extend(core::iter::repeat_n('#', filled));
+            line.extend(core::iter::repeat_n('-', BAR_WIDTH - filled));
+            line.push_str("] ");
+        }
+        let bytes = |bytes| Bytes {
+            bytes,
+            units: self.units,
+        };
+        let _ = write!(line, "{}", bytes(written));
+        if let Some(total) = progress.total {
+            let _ = write!(line, " / {}", bytes(total));
+            if !self.terminal {
+                let percent = self.monitor.fraction().unwrap_or(0.0) * 100.0;
+                let _ = write!(line, " ({percent:.0}%)");
+            }
+        }
+        let rate = BytesPerSecond {
+            bytes_per_second: progress.rate,
+            units: self.units,
+        };
+        let _ = write!(line, "  {rate}");
+        if let Some(eta) = self.monitor.eta().filter(|_| !self.monitor.is_finished()) {
+            let _ = write!(line, "  ETA {}", HumanDuration(eta));
+        }
+    }
+}
+
+impl Monitor {
+    /// Draw the progress to stderr on a background thread, see [`Renderer`].
+    pub fn render(&self) -> JoinHandle<io::Result<()>> {
+        Renderer::new(self.clone()).spawn()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Renderer;
+    use crate::WriteMonitor;
+    use std::io::{self, Write};
+    use std::sync::{Arc, Mutex};
+    use std::time::Duration;
+
+    #[derive(Clone, Default)]
+    struct Shared(Arc<Mutex<Vec<u8>>>);
+
+    impl Write for Shared {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            Ok(())
+        }
+    }
+
+    fn last_line(output: &Shared) -> String {
+        let output = String::from_utf8(output.0.lock().unwrap().clone()).unwrap();
+        output.lines().last().unwrap_or_default().into()
+    }
+
+    #[test]
+    fn ends_with_the_outcome() {
+        let output = Shared::default();
+        let mut wm = WriteMonitor::with_total(io::sink(), 4);
+        let renderer = Renderer::new(wm.monitor())
+            .with_output(output.clone())
+            .refresh(Duration::from_secs(60))
+            .spawn();
+        wm.write_all(b"abcd").unwrap();
+        drop(wm);
+        renderer.join().unwrap().unwrap();
+        assert!(
+            last_line(&output).ends_with("  done"),
+            "{}",
+            last_line(&output)
+        );
+    }
+
+    #[test]
+    fn tells_why_it_was_cancelled() {
+        let output = Shared::default();
+        let mut wm = WriteMonitor::new(io::sink());
+        let renderer = Renderer::new(wm.monitor())
+            .with_output(output.clone())
+            .spawn();
+        wm.monitor().cancel_with_reason("no space");
+        assert!(wm.write_all(b"abcd").is_err());
+        drop(wm);
+        renderer.join().unwrap().unwrap();
+        let line = last_line(&output);
+        assert!(line.ends_with("  cancelled: no space"), "{line}");
+    }
+}
@@ -0,0 +1,467 @@
+use alloc::sync::Arc;
+use core::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+
+/// Sentinel stored in [`State::total`] while the total is unknown.
+const UNKNOWN: u64 = u64::MAX;
+
+/// The state shared between a monitored writer (or reader) and all of its [`Monitor`](crate::Monitor)s.
+#[derive(Debug)]
+pub(crate) struct State {
+    pub(crate) bytes_written: Arc<AtomicU64>,
+    /// Bytes confirmed by a successful flush, shutdown or close.
+    flushed: AtomicU64,
+    /// Length of the start of the stream known to be on disk after a `sync_data`.
+    synced: AtomicU64,
+    total: AtomicU64,
+    /// Distinct bytes of the stream that were written, i.e. `bytes_written` without overwrites.
+    unique: AtomicU64,
+    position: AtomicU64,
+    /// The furthest position written up to.
+    high_water: AtomicU64,
+    /// The parts of the stream that were written, only kept up to date once the writer seeked.
+    ///
+    /// After a seek `position` and `high_water` only change while it is locked.
+    #[cfg(feature = "std")]
+    written: std::sync::Mutex<crate::extent::Written>,
+    /// Whether the writer moved away from the end of what it wrote,
+    /// until then the written part of the stream is everything up to `high_water`.
+    #[cfg(feature = "std")]
+    seeked: AtomicBool,
+    /// Number of live [`Handle`]s.
+    writers: AtomicUsize,
+    /// Number of writers taken apart with [`Handle::park`] that may come back.
+    parked: AtomicUsize,
+    finished: AtomicBool,
+    paused: AtomicBool,
+    #[cfg(feature = "std")]
+    pub(crate) lifecycle: crate::lifecycle::Tracker,
+    #[cfg(feature = "std")]
+    pub(crate) errors: crate::errors::Errors,
+    #[cfg(feature = "std")]
+    pub(crate) cancel: crate::cancel::Cancellation,
+    #[cfg(feature = "std")]
+    pub(crate) quota: crate::quota::Quota,
+    #[cfg(feature = "std")]
+    pub(crate) hooks: crate::hooks::Hooks,
+    #[cfg(feature = "std")]
+    pub(crate) limiter: crate::limit::Limiter,
+    #[cfg(feature = "std")]
+    pub(crate) bandwidth: std::sync::OnceLock<crate::bandwidth::Membership>,
+    #[cfg(feature = "std")]
+    pub(crate) node: crate::aggregate::Node,
+    #[cfg(feature = "stats")]
+    pub(crate) stats: crate::stats::Counters,
+    #[cfg(feature = "std")]
+    pub(crate) meter: crate::rate::Meter,
+    #[cfg(feature = "std")]
+    pub(crate) notifier: crate::wait::Notifier,
+}
+
+impl State {
+    #[cfg(not(feature = "std"))]
+    pub(crate) fn new() -> Self {
+        Self::build()
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn new() -> Self {
+        Self::with_clock(Arc::new(crate::SystemClock::new()))
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn with_clock(clock: Arc<dyn crate::Clock>) -> Self {
+        Self::build(clock)
+    }
+
+    fn build(#[cfg(feature = "std")] clock: Arc<dyn crate::Clock>) -> Self {
+        Self {
+            bytes_written: Arc::new(AtomicU64::new(0)),
+            flushed: AtomicU64::new(0),
+            synced: AtomicU64::new(0),
+            total: AtomicU64::new(UNKNOWN),
+            unique: AtomicU64::new(0),
+            position: AtomicU64::new(0),
+            high_water: AtomicU64::new(0),
+            #[cfg(feature = "std")]
+            written: Default::default(),
+            #[cfg(feature = "std")]
+            seeked: AtomicBool::new(false),
+            writers: AtomicUsize::new(0),
+            parked: AtomicUsize::new(0),
+            finished: AtomicBool::new(false),
+            paused: AtomicBool::new(false),
+            #[cfg(feature = "std")]
+            lifecycle: Default::default(),
+            #[cfg(feature = "std")]
+            errors: Default::default(),
+            #[cfg(feature = "std")]
+            cancel: Default::default(),
+            #[cfg(feature = "std")]
+            quota: Default::default(),
+            #[cfg(feature = "std")]
+            hooks: Default::default(),
+            #[cfg(feature = "std")]
+            limiter: Default::default(),
+            #[cfg(feature = "std")]
+            bandwidth: Default::default(),
+            #[cfg(feature = "std")]
+            node: Default::default(),
+            #[cfg(feature = "stats")]
+            stats: Default::default(),
+            #[cfg(feature = "std")]
+            meter: crate::rate::Meter::new(clock),
+            #[cfg(feature = "std")]
+            notifier: Default::default(),
+        }
+    }
+
+    /// A fresh state with the same total, clock and limits.
+    pub(crate) fn fork(&self) -> Self {
+        #[cfg(not(feature = "std"))]
+        let this = Self::new();
+        #[cfg(feature = "std")]
+        let this = Self::with_clock(self.meter.clock());
+        let total = self.total.load(Ordering::Acquire);
+        this.total.store(total, Ordering::Release);
+        #[cfg(feature = "std")]
+        {
+            this.limiter.set(self.limiter.get(), this.meter.now());
+            if let Some(limit) = self.quota.get() {
+                this.quota.set(limit, self.quota.policy());
+            }
+        }
+        this
+    }
+
+    pub(crate) fn bytes_written(&self) -> u64 {
+        self.bytes_written.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn bytes_flushed(&self) -> u64 {
+        self.flushed.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn bytes_synced(&self) -> u64 {
+        self.synced.load(Ordering::Acquire)
+    }
+
+    /// `bytes` distinct bytes of the stream were flushed.
+    #[cfg(feature = "std")]
+    pub(crate) fn flushed_up_to(&self, bytes: u64) {
+        self.reached(|state| &state.flushed, bytes)
+    }
+
+    /// The first `bytes` bytes of the stream are on disk.
+    #[cfg(feature = "std")]
+    pub(crate) fn synced_up_to(&self, bytes: u64) {
+        self.reached(|state| &state.synced, bytes)
+    }
+
+    /// Length of the start of the stream that was written without holes.
+    #[cfg(feature = "std")]
+    pub(crate) fn written_prefix(&self) -> u64 {
+        self.written().prefix()
+    }
+
+    /// Move `counter` up to `bytes` and the same counter of the parents by as much.
+    #[cfg(feature = "std")]
+    fn reached(&self, counter: fn(&State) -> &AtomicU64, bytes: u64) {
+        let old = counter(self).fetch_max(bytes, Ordering::AcqRel);
+        if bytes > old {
+            self.add_reached(counter, bytes - old);
+        }
+    }
+
+    #[cfg(feature = "std")]
+    fn add_reached(&self, counter: fn(&State) -> &AtomicU64, n: u64) {
+        if let Some(parent) = self.node.parent() {
+            counter(parent).fetch_add(n, Ordering::AcqRel);
+            parent.add_reached(counter, n);
+        }
+        self.notify();
+    }
+
+    pub(crate) fn unique_bytes_written(&self) -> u64 {
+        self.unique.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn position(&self) -> u64 {
+        self.position.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn high_water_mark(&self) -> u64 {
+        self.high_water.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn total(&self) -> Option<u64> {
+        match self.total.load(Ordering::Acquire) {
+            UNKNOWN => None,
+            total => Some(total),
+        }
+    }
+
+    pub(crate) fn set_total(&self, total: Option<u64>) {
+        let total = total.map_or(UNKNOWN, |total| total.min(UNKNOWN - 1));
+        let old = self.total.swap(total, Ordering::AcqRel);
+        #[cfg(feature = "std")]
+        {
+            self.hooks.rearm();
+            if let Some(parent) = self.node.parent() {
+                let known = |total| (total != UNKNOWN).then_some(total);
+                parent.child_total_changed(known(old), known(total));
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = old;
+        self.notify();
+    }
+
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn is_complete(&self) -> bool {
+        self.total()
+            .is_some_and(|total| self.unique_bytes_written() >= total)
+    }
+
+    /// The writer moved to `position`, skipped bytes only count once they are written.
+    #[cfg(feature = "std")]
+    pub(crate) fn record_seek(&self, position: u64) {
+        let written = self.written();
+        if position != self.high_water_mark() {
+            self.seeked.store(true, Ordering::Release);
+        }
+        self.position.store(position, Ordering::Release);
+        drop(written);
+        self.notify();
+    }
+
+    /// Lock the written parts of the stream, bringing them up to date if the writer never seeked.
+    #[cfg(feature = "std")]
+    fn written(&self) -> std::sync::MutexGuard<'_, crate::extent::Written> {
+        let mut written = self.written.lock().unwrap_or_else(|e| e.into_inner());
+        if !self.seeked.load(Ordering::Acquire) {
+            written.reset(self.high_water_mark());
+        }
+        written
+    }
+
+    /// Whether the writer was shut down, closed or dropped, no more bytes will be recorded.
+    pub(crate) fn is_finished(&self) -> bool {
+        self.finished.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn finish(&self) {
+        if self.finished.swap(true, Ordering::AcqRel) {
+            return;
+        }
+        #[cfg(feature = "std")]
+        if let Some(group) = self.bandwidth.get() {
+            group.leave();
+        }
+        self.notify();
+    }
+
+    pub(crate) fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::Acquire)
+    }
+
+    pub(crate) fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::AcqRel) != paused {
+            self.notify();
+        }
+    }
+
+    #[cfg(feature = "std")]
+    pub(crate) fn set_rate_limit(&self, limit: Option<crate::RateLimit>) {
+        self.limiter.set(limit, self.meter.now());
+        self.notify();
+    }
+
+    /// Wake everyone waiting on a change of this state.
+    pub(crate) fn notify(&self) {
+        #[cfg(feature = "std")]
+        self.notifier.notify();
+    }
+
+    /// Account for `n` bytes that were accepted by the inner writer (or handed out by the inner reader).
+    #[cfg(feature = "std")]
+    pub(crate) fn record(&self, n: u64) {
+        if !self.seeked.load(Ordering::Acquire) {
+            // Appending, all of it is new.
+            let end = self
+                .position
+                .fetch_add(n, Ordering::AcqRel)
+                .saturating_add(n);
+            self.high_water.fetch_max(end, Ordering::AcqRel);
+            return self.add(n, n);
+        }
+        let mut written = self.written();
+        let start = self.position.load(Ordering::Acquire);
+        let end = start.saturating_add(n);
+        self.position.store(end, Ordering::Release);
+        self.high_water.fetch_max(end, Ordering::AcqRel);
+        let unique = written.insert(start..end);
+        drop(written);
+        self.add(n, unique);
+    }
+
+    /// Move all counters to `offset`, as if exactly `offset` bytes had been written so far.
+    ///
+    /// Flushed and synced bytes before `offset` stay flushed and synced, those past it no longer count.
+    pub(crate) fn rebase(&self, offset: u64) {
+        self.move_to(offset, |durable| durable.min(offset))
+    }
+
+    /// Start at `offset` for a resumed transfer, everything before it counts as flushed and synced.
+    pub(crate) fn resume_at(&self, offset: u64) {
+        self.move_to(offset, |_| offset)
+    }
+
+    /// Move the written counters to `offset` and the flushed and synced ones to what `durable` makes of them.
+    fn move_to(&self, offset: u64, durable: impl Fn(u64) -> u64) {
+        #[cfg(feature = "std")]
+        let mut written = self.written();
+        let bytes = self.bytes_written.swap(offset, Ordering::AcqRel);
+        let unique = self.unique.swap(offset, Ordering::AcqRel);
+        let durable = |counter: &AtomicU64| {
+            let old = counter
+                .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| Some(durable(n)))
+                .unwrap_or_else(|n| n);
+            (old, durable(old))
+        };
+        let flushed = durable(&self.flushed);
+        let synced = durable(&self.synced);
+        self.position.store(offset, Ordering::Release);
+        self.high_water.store(offset, Ordering::Release);
+        #[cfg(feature = "std")]
+        {
+            written.reset(offset);
+            self.seeked.store(false, Ordering::Release);
+            drop(written);
+        }
+        let counters = [(bytes, offset), (unique, offset), flushed, synced];
+        self.rebased(counters);
+    }
+
+    /// A child state was rebased, move the counters by as much as the child's did.
+    #[cfg(feature = "std")]
+    fn shift(&self, [bytes, unique, flushed, synced]: [(u64, u64); 4]) {
+        self.rebased([
+            shift(&self.bytes_written, bytes),
+            shift(&self.unique, unique),
+            shift(&self.flushed, flushed),
+            shift(&self.synced, synced),
+        ]);
+    }
+
+    /// The bytes written, unique, flushed and synced counters moved `from` `to` without anything being written.
+    fn rebased(&self, counters: [(u64, u64); 4]) {
+        #[cfg(feature = "std")]
+        {
+            let (from, to) = counters[0];
+            self.meter.rebase(from, to);
+            self.hooks.rebase(to, counters[1].1, self.total());
+            if let Some(parent) = self.node.parent() {
+                parent.shift(counters);
+            }
+        }
+        #[cfg(not(feature = "std"))]
+        let _ = counters;
+        self.notify();
+    }
+
+    /// Add `n` bytes, `unique` of which were not written before, written here or by a child state.
+    #[cfg_attr(not(feature = "std"), allow(dead_code))]
+    pub(crate) fn add(&self, n: u64, unique: u64) {
+        self.bytes_written.fetch_add(n, Ordering::AcqRel);
+        self.unique.fetch_add(unique, Ordering::AcqRel);
+        #[cfg(feature = "std")]
+        {
+            self.meter.start();
+            self.lifecycle.active();
+            if let Some(group) = self.bandwidth.get() {
+                group.record(n);
+            }
+            if let Some(parent) = self.node.parent() {
+                parent.add(n, unique);
+            }
+        }
+        self.notify();
+        #[cfg(feature = "std")]
+        self.run_hooks();
+    }
+}
+
+/// Add `to - from` to `counter`, returning its old and new value.
+#[cfg(feature = "std")]
+fn shift(counter: &AtomicU64, (from, to): (u64, u64)) -> (u64, u64) {
+    let moved = |n: u64| n.saturating_add(to).saturating_sub(from);
+    let old = counter
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| Some(moved(n)))
+        .unwrap_or_else(|n| n);
+    (old, moved(old))
+}
+
+/// The writer (or reader) side's reference to the [`State`].
+///
+/// Dropping the last `Handle` finishes the state so observers know no more bytes are coming.
+#[derive(Debug)]
+pub(crate) struct Handle(Arc<State>);
+
+impl Handle {
+    pub(crate) fn new(state: State) -> Self {
+        Self::attach(Arc::new(state))
+    }
+
+    pub(crate) fn attach(state: Arc<State>) -> Self {
+        state.writers.fetch_add(1, Ordering::AcqRel);
+        Self(state)
+    }
+
+    pub(crate) fn shared(&self) -> Arc<State> {
+        self.0.clone()
+    }
+
+    /// Let go of the state without finishing it, [`Handle::unpark`] picks it up again.
+    pub(crate) fn park(self) -> Arc<State> {
+        let state = self.shared();
+        state.parked.fetch_add(1, Ordering::AcqRel);
+        state
+    }
+
+    pub(crate) fn unpark(state: Arc<State>) -> Self {
+        let this = Self::attach(state);
+        let _ = this
+            .parked
+            .fetch_update(Ordering::AcqRel, Ordering::Acquire, |n| n.checked_sub(1));
+        this
+    }
+}
+
+impl Clone for Handle {
+    fn clone(&self) -> Self {
+        Self::attach(self.shared())
+    }
+}
+
+impl Drop for Handle {
+    fn drop(&mut self) {
+        let last = self.0.writers.fetch_sub(1, Ordering::AcqRel) == 1;
+        if last && self.0.parked.load(Ordering::Acquire) == 0 {
+            #[cfg(feature = "std")]
+            self.0.lifecycle.dropped(self.0.is_complete());
+            self.0.finish();
+            #[cfg(feature = "std")]
+            if self.0.lifecycle.get().is_closed() {
+                self.0.run_complete_hooks();
+            }
+        }
+    }
+}
+
+impl core::ops::Deref for Handle {
+    type Target = State;
+
+    fn deref(&self) -> &State {
+        &self.0
+    }
+}
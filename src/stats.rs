@@ -0,0 +1,152 @@
+//! Per-call statistics of a [`WriteMonitor`](crate::WriteMonitor), enabled with the `stats` feature.
+use crate::Monitor;
+use core::ops::RangeInclusive;
+use core::sync::atomic::{AtomicU64, Ordering};
+
+/// Bucket `0` holds empty writes, bucket `i` holds writes of `2^(i-1)..=2^i - 1` bytes.
+const BUCKETS: usize = 65;
+
+#[derive(Debug)]
+pub(crate) struct Counters {
+    write_calls: AtomicU64,
+    short_writes: AtomicU64,
+    flushes: AtomicU64,
+    histogram: [AtomicU64; BUCKETS],
+}
+
+impl Default for Counters {
+    fn default() -> Self {
+        Self {
+            write_calls: AtomicU64::new(0),
+            short_writes: AtomicU64::new(0),
+            flushes: AtomicU64::new(0),
+            histogram: core::array::from_fn(|_| AtomicU64::new(0)),
+        }
+    }
+}
+
+impl Counters {
+    /// Account for a write given `requested` bytes by the caller that accepted `written` of them, `None` if it failed.
+    pub(crate) fn write(&self, requested: usize, written: Option<usize>) {
+        self.write_calls.fetch_add(1, Ordering::Relaxed);
+        if let Some(n) = written {
+            if n < requested {
+                self.short_writes.fetch_add(1, Ordering::Relaxed);
+            }
+            self.histogram[bucket(n as u64)].fetch_add(1, Ordering::Relaxed);
+        }
+    }
+
+    pub(crate) fn flush(&self) {
+        self.flushes.fetch_add(1, Ordering::Relaxed);
+    }
+
+    fn snapshot(&self) -> WriteStats {
+        WriteStats {
+            write_calls: self.write_calls.load(Ordering::Relaxed),
+            short_writes: self.short_writes.load(Ordering::Relaxed),
+            flushes: self.flushes.load(Ordering::Relaxed),
+            histogram: core::array::from_fn(|i| self.histogram[i].load(Ordering::Relaxed)),
+        }
+    }
+}
+
+fn bucket(size: u64) -> usize {
+    (u64::BITS - size.leading_zeros()) as usize
+}
+
+fn bucket_range(bucket: usize) -> RangeInclusive<u64> {
+    match bucket {
+        0 => 0..=0,
+        64 => 1 << 63..=u64::MAX,
+        i => 1 << (i - 1)..=(1 << i) - 1,
+    }
+}
+
+/// A snapshot of the per-call statistics, see [`Monitor::stats`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WriteStats {
+    /// Number of completed write calls, including failed ones.
+    pub write_calls: u64,
+    /// Number of writes that accepted fewer bytes than they were given,
+    /// including writes cut short by a [`RateLimit`](crate::RateLimit) or a limit on the size.
+    pub short_writes: u64,
+    /// Number of completed flush calls.
+    pub flushes: u64,
+    histogram: [u64; BUCKETS],
+}
+
+impl WriteStats {
+    /// The sizes of successful writes as `(size range, count)` pairs in log2 buckets, skipping empty buckets.
+    pub fn histogram(&self) -> impl Iterator<Item = (RangeInclusive<u64>, u64)> + '_ {
+        self.histogram
+            .iter()
+            .enumerate()
+            .filter(|(_, &count)| count > 0)
+            .map(|(i, &count)| (bucket_range(i), count))
+    }
+
+    /// Number of successful writes whose size fell into `size`'s bucket.
+    pub fn writes_near(&self, size: u64) -> u64 {
+        self.histogram[bucket(size)]
+    }
+}
+
+#[cfg_attr(docsrs, doc(cfg(feature = "stats")))]
+impl Monitor {
+    /// A snapshot of the write call count, short writes, flushes and write sizes.
+    pub fn stats(&self) -> WriteStats {
+        self.state.stats.snapshot()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{bucket, bucket_range};
+    use crate::{QuotaPolicy, WriteMonitor};
+    use std::io::Write;
+
+    #[test]
+    fn buckets() {
+        assert_eq!(bucket(0), 0);
+        assert_eq!(bucket(1), 1);
+        assert_eq!(bucket(2), 2);
+        assert_eq!(bucket(3), 2);
+        assert_eq!(bucket(4), 3);
+        assert_eq!(bucket(u64::MAX), 64);
+        for size in [0, 1, 5, 4096, 4097, u64::MAX] {
+            assert!(bucket_range(bucket(size)).contains(&size));
+        }
+    }
+
+    #[test]
+    fn counts_writes_flushes_and_sizes() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let monitor = wm.monitor();
+        wm.write_all(&[0; 100]).unwrap();
+        wm.write_all(&[0; 3]).unwrap();
+        wm.write_all(&[]).unwrap();
+        wm.flush().unwrap();
+        let stats = monitor.stats();
+        assert_eq!(stats.write_calls, 2);
+        assert_eq!(stats.short_writes, 0);
+        assert_eq!(stats.flushes, 1);
+        assert_eq!(stats.writes_near(100), 1);
+        assert_eq!(stats.writes_near(65), 1);
+        assert_eq!(
+            stats.histogram().collect::<Vec<_>>(),
+            [(2..=3, 1), (64..=127, 1)]
+        );
+    }
+
+    #[test]
+    fn writes_truncated_by_the_monitor_are_short() {
+        let mut wm = WriteMonitor::with_limit(Vec::new(), 4, QuotaPolicy::Truncate);
+        let monitor = wm.monitor();
+        assert_eq!(wm.write(b"hello").unwrap(), 4);
+        let stats = monitor.stats();
+        assert_eq!(stats.write_calls, 1);
+        assert_eq!(stats.short_writes, 1);
+        assert_eq!(stats.writes_near(4), 1);
+    }
+}
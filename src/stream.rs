@@ -0,0 +1,215 @@
+//! Streams of [`Progress`] events.
+#[cfg(feature = "futures")]
+use crate::timer::{Sleep, ThreadTimer, Timer};
+use crate::{Monitor, Progress};
+#[cfg(feature = "futures")]
+use alloc::sync::Arc;
+#[cfg(feature = "futures")]
+use core::pin::Pin;
+use core::sync::atomic::Ordering;
+use core::task::{Context, Poll};
+use std::time::Duration;
+
+impl Monitor {
+    /// Whether something changed since this handle last yielded an event, without marking it as seen.
+    fn has_unseen_change(&self) -> bool {
+        self.seen.load(Ordering::Acquire) != self.state.notifier.generation()
+    }
+
+    /// Take a progress snapshot and mark the current state as seen.
+    fn observe(&self) -> Progress {
+        self.seen
+            .store(self.state.notifier.generation(), Ordering::Release);
+        self.progress()
+    }
+
+    /// A [`Stream`](futures::Stream) of [`Progress`] events driven by the writer.
+    ///
+    /// Events are yielded as bytes are written, but at most once per `interval`;
+    /// changes in between are coalesced into the next event.
+    /// The last event always reflects the final state and the stream ends once the writer is shut down, closed or dropped.
+    ///
+    /// Coalesced changes are yielded when the interval is over, waiting with a [`ThreadTimer`]
+    /// unless another [`Timer`] is set with [`ProgressStream::with_timer`].
+    #[cfg(feature = "futures")]
+    pub fn stream(&self, interval: Duration) -> ProgressStream {
+        ProgressStream {
+            monitor: self.clone(),
+            interval,
+            last: None,
+            timer: Arc::new(ThreadTimer),
+            delay: None,
+            done: false,
+        }
+    }
+
+    /// A stream of [`Progress`] events driven by a [`tokio::time::Interval`].
+    ///
+    /// On every tick an event is yielded if anything changed since the previous one.
+    /// The stream ends on the first tick after the writer is shut down or dropped, yielding the final state.
+    /// This needs to be polled inside a tokio runtime with the time driver enabled.
+    #[cfg(feature = "tokio")]
+    pub fn tokio_stream(&self, interval: Duration) -> TokioProgressStream {
+        let mut interval = tokio::time::interval(interval);
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Skip);
+        TokioProgressStream {
+            monitor: self.clone(),
+            interval,
+            done: false,
+        }
+    }
+}
+
+/// Created by [`Monitor::stream`].
+#[cfg(feature = "futures")]
+pub struct ProgressStream {
+    monitor: Monitor,
+    interval: Duration,
+    /// Clock reading when the last event was yielded.
+    last: Option<Duration>,
+    timer: Arc<dyn Timer>,
+    /// The wait for the rest of the interval while a change is held back.
+    delay: Option<Sleep>,
+    done: bool,
+}
+
+#[cfg(feature = "futures")]
+impl core::fmt::Debug for ProgressStream {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        f.debug_struct("ProgressStream")
+            .field("monitor", &self.monitor)
+            .field("interval", &self.interval)
+            .field("last", &self.last)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+#[cfg(feature = "futures")]
+impl ProgressStream {
+    /// Wait for the end of the interval with `timer`, for example a [`TokioTimer`](crate::TokioTimer).
+    pub fn with_timer(self, timer: impl Timer + 'static) -> Self {
+        Self {
+            timer: Arc::new(timer),
+            delay: None,
+            ..self
+        }
+    }
+}
+
+#[cfg(feature = "futures")]
+impl futures::Stream for ProgressStream {
+    type Item = Progress;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Progress>> {
+        let this = self.get_mut();
+        if this.done {
+            return Poll::Ready(None);
+        }
+        let state = &this.monitor.state;
+        loop {
+            let generation = state.notifier.generation();
+            if state.is_finished() {
+                this.done = true;
+                return Poll::Ready(Some(this.monitor.observe()));
+            }
+            if this.monitor.has_unseen_change() {
+                let now = state.meter.now();
+                let wait = this.last.map_or(Duration::ZERO, |last| {
+                    this.interval.saturating_sub(now.saturating_sub(last))
+                });
+                // Held back changes are yielded once the rest of the interval has passed.
+                let due = wait.is_zero() || {
+                    let timer = &this.timer;
+                    let delay = this.delay.get_or_insert_with(|| timer.sleep(wait));
+                    delay.as_mut().poll(cx).is_ready()
+                };
+                if due {
+                    this.delay = None;
+                    this.last = Some(now);
+                    return Poll::Ready(Some(this.monitor.observe()));
+                }
+            }
+            if state.notifier.register(cx.waker(), generation) {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+/// Created by [`Monitor::tokio_stream`].
+#[cfg(feature = "tokio")]
+#[derive(Debug)]
+pub struct TokioProgressStream {
+    monitor: Monitor,
+    interval: tokio::time::Interval,
+    done: bool,
+}
+
+#[cfg(feature = "tokio")]
+impl TokioProgressStream {
+    pub fn poll_next(&mut self, cx: &mut Context<'_>) -> Poll<Option<Progress>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        loop {
+            if self.interval.poll_tick(cx).is_pending() {
+                return Poll::Pending;
+            }
+            if self.monitor.is_finished() {
+                self.done = true;
+                return Poll::Ready(Some(self.monitor.observe()));
+            }
+            if self.monitor.has_unseen_change() {
+                return Poll::Ready(Some(self.monitor.observe()));
+            }
+        }
+    }
+
+    /// Wait for the next event, `None` once the stream has ended.
+    pub async fn next(&mut self) -> Option<Progress> {
+        core::future::poll_fn(|cx| self.poll_next(cx)).await
+    }
+}
+
+#[cfg(all(feature = "tokio", feature = "futures"))]
+impl futures::Stream for TokioProgressStream {
+    type Item = Progress;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Progress>> {
+        TokioProgressStream::poll_next(self.get_mut(), cx)
+    }
+}
+
+#[cfg(all(test, feature = "futures"))]
+mod tests {
+    use crate::WriteMonitor;
+    use futures::{executor::block_on, StreamExt};
+    use std::io::Write;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn held_back_change_is_yielded_after_the_interval() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let mut stream = wm.monitor().stream(Duration::from_millis(200));
+        wm.write_all(b"a").unwrap();
+        assert_eq!(block_on(stream.next()).unwrap().bytes, 1);
+        wm.write_all(b"bc").unwrap();
+        let start = Instant::now();
+        assert_eq!(block_on(stream.next()).unwrap().bytes, 3);
+        let waited = start.elapsed();
+        assert!(waited >= Duration::from_millis(150), "{waited:?}");
+        assert!(waited < Duration::from_secs(1), "{waited:?}");
+    }
+
+    #[test]
+    fn ends_with_the_final_state() {
+        let mut wm = WriteMonitor::new(Vec::new());
+        let stream = wm.monitor().stream(Duration::from_secs(60));
+        wm.write_all(b"hello").unwrap();
+        drop(wm);
+        let events: Vec<_> = block_on(stream.collect());
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].bytes, 5);
+    }
+}
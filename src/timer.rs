@@ -0,0 +1,175 @@
+//! Timers used by the async write paths to wait, for example for a [`RateLimit`](crate::RateLimit).
+use alloc::boxed::Box;
+use alloc::sync::{Arc, Weak};
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+use std::sync::{Condvar, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+
+/// A future returned by [`Timer::sleep`].
+pub type Sleep = Pin<Box<dyn Future<Output = ()> + Send + Sync>>;
+
+/// Creates futures that resolve after a duration.
+///
+/// `futures` has no timer of its own, so the [`futures::io::AsyncWrite`] impl of
+/// [`WriteMonitor`](crate::WriteMonitor) uses [`ThreadTimer`] unless another one is set with
+/// [`WriteMonitor::set_timer`](crate::WriteMonitor::set_timer).
+/// The [`tokio::io::AsyncWrite`] impl defaults to [`TokioTimer`].
+pub trait Timer: Send + Sync {
+    fn sleep(&self, duration: Duration) -> Sleep;
+}
+
+/// A [`Timer`] served by a single background thread shared by all sleeps.
+///
+/// It works with any executor, but a timer from the executor in use is more efficient.
+/// The thread is started on the first sleep that is polled and lives as long as the process.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct ThreadTimer;
+
+impl Timer for ThreadTimer {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(ThreadSleep {
+            deadline: Instant::now().checked_add(duration),
+            shared: None,
+        })
+    }
+}
+
+#[derive(Debug, Default)]
+struct Wakeup {
+    done: bool,
+    waker: Option<Waker>,
+}
+
+struct ThreadSleep {
+    /// `None` for a sleep too long to ever end.
+    deadline: Option<Instant>,
+    /// Set once the sleep was handed to the timer thread on the first poll.
+    shared: Option<Arc<Mutex<Wakeup>>>,
+}
+
+impl Future for ThreadSleep {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let this = self.get_mut();
+        let deadline = this.deadline;
+        let shared = this.shared.get_or_insert_with(|| {
+            let shared = Arc::new(Mutex::new(Wakeup::default()));
+            if let Some(deadline) = deadline {
+                TimerThread::get().add(deadline, &shared);
+            }
+            shared
+        });
+        let mut wakeup = shared.lock().unwrap_or_else(|e| e.into_inner());
+        if wakeup.done {
+            return Poll::Ready(());
+        }
+        wakeup.waker = Some(cx.waker().clone());
+        Poll::Pending
+    }
+}
+
+/// The thread behind [`ThreadTimer`] and the sleeps it has to end.
+#[derive(Default)]
+struct TimerThread {
+    /// Sleeps waiting for their deadline, dropped sleeps are only let go once it passed.
+    sleeps: Mutex<Vec<(Instant, Weak<Mutex<Wakeup>>)>>,
+    added: Condvar,
+}
+
+impl TimerThread {
+    fn get() -> &'static TimerThread {
+        static THREAD: OnceLock<TimerThread> = OnceLock::new();
+        THREAD.get_or_init(|| {
+            std::thread::Builder::new()
+                .name("write-monitor-timer".into())
+                .spawn(|| TimerThread::get().run())
+                .expect("failed to spawn the timer thread");
+            TimerThread::default()
+        })
+    }
+
+    fn add(&self, deadline: Instant, wakeup: &Arc<Mutex<Wakeup>>) {
+        let mut sleeps = self.sleeps.lock().unwrap_or_else(|e| e.into_inner());
+        sleeps.push((deadline, Arc::downgrade(wakeup)));
+        self.added.notify_one();
+    }
+
+    fn run(&self) {
+        let mut sleeps = self.sleeps.lock().unwrap_or_else(|e| e.into_inner());
+        loop {
+            let now = Instant::now();
+            sleeps.retain(|(deadline, wakeup)| {
+                if *deadline > now {
+                    return true;
+                }
+                if let Some(wakeup) = wakeup.upgrade() {
+                    let mut wakeup = wakeup.lock().unwrap_or_else(|e| e.into_inner());
+                    wakeup.done = true;
+                    if let Some(waker) = wakeup.waker.take() {
+                        waker.wake();
+                    }
+                }
+                false
+            });
+            sleeps = match sleeps.iter().map(|(deadline, _)| *deadline).min() {
+                Some(next) => {
+                    self.added
+                        .wait_timeout(sleeps, next - now)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.added.wait(sleeps).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+    }
+}
+
+/// A [`Timer`] backed by [`tokio::time::sleep`], it needs a tokio runtime with the time driver enabled.
+#[cfg_attr(docsrs, doc(cfg(feature = "tokio")))]
+#[cfg(feature = "tokio")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TokioTimer;
+
+#[cfg(feature = "tokio")]
+impl Timer for TokioTimer {
+    fn sleep(&self, duration: Duration) -> Sleep {
+        Box::pin(tokio::time::sleep(duration))
+    }
+}
+
+#[cfg(all(test, feature = "futures"))]
+mod tests {
+    use super::{ThreadTimer, Timer};
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn thread_timer_sleeps_share_a_thread() {
+        let start = Instant::now();
+        let sleeps = [300, 100, 200].map(|ms| {
+            let sleep = ThreadTimer.sleep(Duration::from_millis(ms));
+            async move {
+                sleep.await;
+                start.elapsed()
+            }
+        });
+        let [a, b, c] = futures::executor::block_on(async {
+            let [a, b, c] = sleeps;
+            futures::join!(a, b, c)
+        })
+        .into();
+        assert!(a >= Duration::from_millis(300));
+        assert!(b >= Duration::from_millis(100) && b < a);
+        assert!(c >= Duration::from_millis(200) && c < a);
+        // A sleep dropped before its deadline does not hold up the others.
+        futures::executor::block_on(async {
+            let mut long = ThreadTimer.sleep(Duration::from_secs(3600));
+            assert!(futures::poll!(&mut long).is_pending());
+            drop(long);
+            ThreadTimer.sleep(Duration::from_millis(10)).await;
+        });
+    }
+}
@@ -0,0 +1,298 @@
+//! Waiting for changes to a [`Monitor`](crate::Monitor), asynchronously or by blocking the thread.
+use crate::{Lifecycle, Monitor};
+use alloc::vec::Vec;
+use core::future::Future;
+use core::pin::Pin;
+use core::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use core::task::{Context, Poll, Waker};
+use std::sync::{Condvar, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// Wakes tasks and threads waiting on the shared state whenever it changes.
+///
+/// Every change bumps a generation counter, waiters compare it against the generation they last saw.
+/// Writers only take the lock when someone is actually waiting.
+#[derive(Debug, Default)]
+pub(crate) struct Notifier {
+    generation: AtomicU64,
+    waiting: AtomicUsize,
+    wakers: Mutex<Vec<Waker>>,
+    condvar: Condvar,
+}
+
+impl Notifier {
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(Ordering::SeqCst)
+    }
+
+    pub(crate) fn notify(&self) {
+        self.generation.fetch_add(1, Ordering::SeqCst);
+        if self.waiting.load(Ordering::SeqCst) == 0 {
+            return;
+        }
+        let wakers = {
+            let mut wakers = self.lock();
+            self.waiting.fetch_sub(wakers.len(), Ordering::SeqCst);
+            core::mem::take(&mut *wakers)
+        };
+        self.condvar.notify_all();
+        wakers.into_iter().for_each(Waker::wake);
+    }
+
+    /// Register `waker` to be woken on the next change after `generation`.
+    ///
+    /// Returns `false` if the state already changed, in which case nothing was registered.
+    pub(crate) fn register(&self, waker: &Waker, generation: u64) -> bool {
+        let mut wakers = self.lock();
+        if !wakers.iter().any(|w| w.will_wake(waker)) {
+            wakers.push(waker.clone());
+            self.waiting.fetch_add(1, Ordering::SeqCst);
+        }
+        self.generation() == generation
+    }
+
+    /// Block the current thread until the generation moves past `generation` or `deadline` passes.
+    ///
+    /// Returns the current generation.
+    pub(crate) fn block(&self, generation: u64, deadline: Option<Instant>) -> u64 {
+        let mut guard = self.lock();
+        self.waiting.fetch_add(1, Ordering::SeqCst);
+        while self.generation() == generation {
+            guard = match deadline {
+                Some(deadline) => {
+                    let Some(timeout) = deadline.checked_duration_since(Instant::now()) else {
+                        break;
+                    };
+                    self.condvar
+                        .wait_timeout(guard, timeout)
+                        .unwrap_or_else(|e| e.into_inner())
+                        .0
+                }
+                None => self.condvar.wait(guard).unwrap_or_else(|e| e.into_inner()),
+            };
+        }
+        self.waiting.fetch_sub(1, Ordering::SeqCst);
+        drop(guard);
+        self.generation()
+    }
+
+    fn lock(&self) -> MutexGuard<'_, Vec<Waker>> {
+        self.wakers.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Resolves once `check` returns `Some`, re-evaluating it whenever the monitor changes.
+struct Until<'a, F> {
+    monitor: &'a Monitor,
+    check: F,
+}
+
+impl<T, F: FnMut(&Monitor) -> Option<T> + Unpin> Future for Until<'_, F> {
+    type Output = T;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<T> {
+        let this = self.get_mut();
+        let notifier = &this.monitor.state.notifier;
+        loop {
+            let generation = notifier.generation();
+            if let Some(out) = (this.check)(this.monitor) {
+                return Poll::Ready(out);
+            }
+            if notifier.register(cx.waker(), generation) {
+                return Poll::Pending;
+            }
+        }
+    }
+}
+
+impl Monitor {
+    fn until<T, F: FnMut(&Monitor) -> Option<T> + Unpin>(&self, check: F) -> Until<'_, F> {
+        Until {
+            monitor: self,
+            check,
+        }
+    }
+
+    fn blocking_until<T>(
+        &self,
+        deadline: Option<Instant>,
+        mut check: impl FnMut(&Monitor) -> Option<T>,
+    ) -> Option<T> {
+        let notifier = &self.state.notifier;
+        let mut generation = notifier.generation();
+        loop {
+            if let Some(out) = check(self) {
+                return Some(out);
+            }
+            if deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+                return None;
+            }
+            generation = notifier.block(generation, deadline);
+        }
+    }
+
+    /// [`Monitor::blocking_until`] without a deadline.
+    fn blocking<T>(&self, mut check: impl FnMut(&Monitor) -> Option<T>) -> T {
+        loop {
+            if let Some(out) = self.blocking_until(None, &mut check) {
+                return out;
+            }
+        }
+    }
+
+    /// Mark the current state as seen if it changed since this handle last looked.
+    fn take_change(&self) -> Option<()> {
+        let generation = self.state.notifier.generation();
+        (self.seen.swap(generation, Ordering::AcqRel) != generation).then_some(())
+    }
+
+    /// Wait until the monitored state changes.
+    ///
+    /// Changes are tracked per handle, so this returns immediately if something changed since the last call on this `Monitor`.
+    pub async fn changed(&self) {
+        self.until(Self::take_change).await
+    }
+
+    /// Resolve `reached` or, once no more bytes will come, how the writer ended.
+    fn reached_or_ended<T>(&self, reached: Option<T>) -> Option<Result<T, Lifecycle>> {
+        if let Some(out) = reached {
+            return Some(Ok(out));
+        }
+        let lifecycle = self.lifecycle();
+        (self.is_finished() || lifecycle.is_abandoned()).then_some(Err(lifecycle))
+    }
+
+    fn check_bytes(&self, bytes: u64) -> Option<Result<u64, Lifecycle>> {
+        self.reached_or_ended(Some(self.bytes_written()).filter(|&n| n >= bytes))
+    }
+
+    fn check_complete(&self) -> Option<Result<(), Lifecycle>> {
+        self.reached_or_ended(self.is_complete().then_some(()))
+    }
+
+    /// Wait until at least `bytes` have been written and return the current count.
+    ///
+    /// Returns how the writer ended instead if it finishes, fails or is cancelled before that,
+    /// see [`Lifecycle::is_abandoned`].
+    /// ```
+    /// use write_monitor::{Lifecycle, WriteMonitor};
+    /// use std::io::Write;
+    /// let mut wm = WriteMonitor::new(Vec::new());
+    /// let monitor = wm.monitor();
+    /// wm.write_all(b"abc").unwrap();
+    /// drop(wm);
+    /// assert_eq!(monitor.blocking_wait_for(10), Err(Lifecycle::Dropped));
+    /// assert_eq!(monitor.blocking_wait_for(3), Ok(3));
+    /// ```
+    pub async fn wait_for(&self, bytes: u64) -> Result<u64, Lifecycle> {
+        self.until(move |m| m.check_bytes(bytes)).await
+    }
+
+    /// Wait until the total is known and has been written.
+    ///
+    /// Returns how the writer ended instead if it finishes, fails or is cancelled before that.
+    pub async fn wait_complete(&self) -> Result<(), Lifecycle> {
+        self.until(Self::check_complete).await
+    }
+
+    /// Blocking version of [`Monitor::changed`].
+    pub fn blocking_changed(&self) {
+        self.blocking(Self::take_change)
+    }
+
+    /// Like [`Monitor::blocking_changed`] but gives up after `timeout`, returning whether something changed.
+    pub fn blocking_changed_timeout(&self, timeout: Duration) -> bool {
+        self.blocking_until(Instant::now().checked_add(timeout), Self::take_change)
+            .is_some()
+    }
+
+    /// Blocking version of [`Monitor::wait_for`].
+    pub fn blocking_wait_for(&self, bytes: u64) -> Result<u64, Lifecycle> {
+        self.blocking(|m| m.check_bytes(bytes))
+    }
+
+    /// Blocking version of [`Monitor::wait_complete`].
+    pub fn blocking_wait_complete(&self) -> Result<(), Lifecycle> {
+        self.blocking(Self::check_complete)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Lifecycle, WriteMonitor};
+    use std::io::Write;
+    use std::time::Duration;
+
+    fn block_on<F: core::future::Future>(f: F) -> F::Output {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+            .block_on(f)
+    }
+
+    /// Run `f` on the writer from another thread after a while.
+    fn later(
+        mut wm: WriteMonitor<Vec<u8>>,
+        f: impl FnOnce(&mut WriteMonitor<Vec<u8>>) + Send + 'static,
+    ) {
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            f(&mut wm);
+        });
+    }
+
+    #[test]
+    fn changed_wakes_on_a_write() {
+        let wm = WriteMonitor::new(Vec::new());
+        let monitor = wm.monitor();
+        later(wm, |wm| wm.write_all(b"abc").unwrap());
+        block_on(monitor.changed());
+        assert!(monitor.bytes_written() > 0);
+    }
+
+    #[test]
+    fn wait_for_resolves_once_reached() {
+        let wm = WriteMonitor::new(Vec::new());
+        let monitor = wm.monitor();
+        later(wm, |wm| {
+            wm.write_all(b"abc").unwrap();
+            wm.write_all(b"def").unwrap();
+            wm.flush().unwrap();
+        });
+        assert_eq!(block_on(monitor.wait_for(5)), Ok(6));
+    }
+
+    #[test]
+    fn wait_for_gives_up_when_the_writer_ends_first() {
+        let wm = WriteMonitor::new(Vec::new());
+        let monitor = wm.monitor();
+        later(wm, |wm| wm.write_all(b"abc").unwrap());
+        assert_eq!(block_on(monitor.wait_for(10)), Err(Lifecycle::Dropped));
+    }
+
+    #[test]
+    fn wait_complete_resolves_once_the_total_is_written() {
+        let wm = WriteMonitor::with_total(Vec::new(), 3);
+        let monitor = wm.monitor();
+        later(wm, |wm| wm.write_all(b"abc").unwrap());
+        assert_eq!(block_on(monitor.wait_complete()), Ok(()));
+    }
+
+    #[test]
+    fn wait_complete_gives_up_on_cancel() {
+        let wm = WriteMonitor::with_total(Vec::new(), 3);
+        let monitor = wm.monitor();
+        let cancel = monitor.clone();
+        later(wm, move |_| cancel.cancel());
+        assert_eq!(block_on(monitor.wait_complete()), Err(Lifecycle::Cancelled));
+        assert_eq!(monitor.blocking_wait_complete(), Err(Lifecycle::Cancelled));
+    }
+
+    #[test]
+    fn blocking_wait_complete_gives_up_when_the_writer_is_dropped() {
+        let wm = WriteMonitor::with_total(Vec::new(), 10);
+        let monitor = wm.monitor();
+        later(wm, |wm| wm.write_all(b"abc").unwrap());
+        assert_eq!(monitor.blocking_wait_complete(), Err(Lifecycle::Dropped));
+    }
+}